@@ -0,0 +1,166 @@
+use crate::parser_error::Error;
+use crate::span::Span;
+use std::fmt::Write;
+
+/// An error ready to be shown to a user, pointing at the offending source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            code,
+            message: message.into(),
+            span,
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic in the familiar compiler style:
+    ///
+    /// ```text
+    /// error[E0001]: Expect ';' after value.
+    ///  --> script.vak:2:8
+    ///   |
+    /// 2 | print 2
+    ///   |        ^
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let line_start = source[..self.span.offset.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |newline| line_start + newline);
+        let line = source[line_start..line_end].trim_end_matches('\r');
+
+        // Keep tabs in the padding so the caret lines up under the source.
+        let padding: String = line
+            .chars()
+            .take(self.span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // Spans running past the end of the line are underlined up to it.
+        let underline_len = line[(self.span.offset - line_start).min(line.len())..]
+            .chars()
+            .take(self.span.len)
+            .map(|c| c.len_utf8())
+            .scan(0, |total, len| {
+                *total += len;
+                Some(*total)
+            })
+            .take_while(|total| *total <= self.span.len)
+            .count()
+            .max(1);
+
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let mut out = String::new();
+        // Writing to a `String` can't fail.
+        let _ = writeln!(out, "error[{}]: {}", self.code, self.message);
+        let _ = writeln!(out, "{}--> {}:{}", gutter, file_name, self.span);
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", line_number, line);
+        let _ = writeln!(out, "{} | {}{}", gutter, padding, "^".repeat(underline_len));
+        for help in &self.help {
+            let _ = writeln!(out, "{} = help: {}", gutter, help);
+        }
+        out
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        let code = match error {
+            Error::ScannerError(..) => "E0000",
+            Error::ParserError(..) => "E0001",
+            Error::ResolverError(..) => "E0002",
+            Error::EvalError(..) => "E0003",
+        };
+        let diagnostic = Diagnostic::new(code, error.message(), error.span());
+        match error.help() {
+            Some(help) => diagnostic.with_help(help),
+            None => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_points_at_span() {
+        let source = "var a = 1;\nprint a +  nil;\n";
+        let span = Span {
+            offset: 22,
+            len: 3,
+            line: 2,
+            column: 12,
+        };
+        let rendered = Diagnostic::new("E0003", "Operand must be a number", span)
+            .with_help("convert the operand first")
+            .render("test.vak", source);
+        assert_eq!(
+            rendered,
+            "error[E0003]: Operand must be a number\n \
+             --> test.vak:2:12\n  \
+             |\n\
+             2 | print a +  nil;\n  \
+             |            ^^^\n  \
+             = help: convert the operand first\n"
+        );
+    }
+
+    #[test]
+    fn test_render_clips_multiline_span_and_keeps_tabs() {
+        let source = "\tprint \"open\nclose\";";
+        let span = Span {
+            offset: 7,
+            len: 12,
+            line: 1,
+            column: 8,
+        };
+        let rendered = Diagnostic::new("E0001", "message", span).render("test.vak", source);
+        assert!(rendered.contains("1 | \tprint \"open\n"), "{}", rendered);
+        assert!(rendered.contains("  | \t      ^^^^^\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_render_empty_span_at_end_of_line() {
+        let source = "print 2";
+        let span = Span {
+            offset: 7,
+            len: 0,
+            line: 1,
+            column: 8,
+        };
+        let rendered = Diagnostic::new("E0001", "message", span).render("test.vak", source);
+        assert!(rendered.ends_with("  |        ^\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_from_error_keeps_help() {
+        let span = Span::default();
+        let error = Error::ResolverError("message".to_string(), span, None).with_help("a hint");
+        assert_eq!(
+            Diagnostic::from(&error),
+            Diagnostic::new("E0002", "message", span).with_help("a hint")
+        );
+        assert!(
+            Diagnostic::from(&Error::EvalError("message".to_string(), span, None))
+                .help
+                .is_empty()
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use crate::expr::Value;
-use std::collections::{hash_map::Entry, HashMap};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Debug, Default)]
 pub struct Env {
     values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Env>>>,
 }
 
 impl Env {
@@ -11,23 +14,122 @@ impl Env {
         Self::default()
     }
 
+    /// Creates a new scope nested inside `enclosing`.
+    pub fn with_enclosing(enclosing: Rc<RefCell<Env>>) -> Self {
+        Env {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
     pub fn define(&mut self, name: impl Into<String>, value: Value) {
         self.values.insert(name.into(), value);
     }
 
-    pub fn get(&self, name: &str) -> Option<&Value> {
-        self.values.get(name)
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Reads a variable from the scope `depth` levels out from this one.
+    pub fn get_at(&self, depth: usize, name: &str) -> Option<Value> {
+        if depth == 0 {
+            return self.values.get(name).cloned();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(depth - 1, name)
     }
 
-    #[allow(dead_code)]
-    pub fn assign(&mut self, name: impl Into<String>, value: Value) -> Result<(), String> {
-        let name = name.into();
-        match self.values.entry(name) {
-            Entry::Occupied(mut e) => {
-                e.insert(value);
-                Ok(())
-            }
-            Entry::Vacant(e) => Err(format!("Undefined variable '{}'", e.key())),
+    /// Updates a variable in the scope `depth` levels out from this one.
+    pub fn assign_at(&mut self, depth: usize, name: &str, value: Value) -> Result<(), String> {
+        if depth == 0 {
+            return match self.values.get_mut(name) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(format!("Undefined variable '{}'", name)),
+            };
+        }
+        match self.enclosing.as_ref() {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(depth - 1, name, value),
+            None => Err(format!("Undefined variable '{}'", name)),
         }
     }
+
+    /// Updates an existing variable in the nearest scope that declares it.
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            return Ok(());
+        }
+        match self.enclosing.as_ref() {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(format!("Undefined variable '{}'", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_walks_enclosing() {
+        let global = Rc::new(RefCell::new(Env::new()));
+        global.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Env::with_enclosing(global);
+        local.define("b", Value::Number(2.0));
+
+        assert_eq!(local.get("a"), Some(Value::Number(1.0)));
+        assert_eq!(local.get("b"), Some(Value::Number(2.0)));
+        assert_eq!(local.get("c"), None);
+    }
+
+    #[test]
+    fn test_define_shadows_enclosing() {
+        let global = Rc::new(RefCell::new(Env::new()));
+        global.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Env::with_enclosing(global.clone());
+        local.define("a", Value::Number(2.0));
+
+        assert_eq!(local.get("a"), Some(Value::Number(2.0)));
+        assert_eq!(global.borrow().get("a"), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_assign_updates_enclosing() {
+        let global = Rc::new(RefCell::new(Env::new()));
+        global.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Env::with_enclosing(global.clone());
+        local.assign("a", Value::Number(2.0)).unwrap();
+
+        assert_eq!(global.borrow().get("a"), Some(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn test_assign_undefined() {
+        let mut env = Env::new();
+        assert_eq!(
+            env.assign("a", Value::Nil),
+            Err("Undefined variable 'a'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_and_assign_at_depth() {
+        let global = Rc::new(RefCell::new(Env::new()));
+        global.borrow_mut().define("a", Value::Number(1.0));
+        let mut local = Env::with_enclosing(global.clone());
+        local.define("a", Value::Number(2.0));
+
+        assert_eq!(local.get_at(0, "a"), Some(Value::Number(2.0)));
+        assert_eq!(local.get_at(1, "a"), Some(Value::Number(1.0)));
+        assert_eq!(local.get_at(2, "a"), None);
+
+        local.assign_at(1, "a", Value::Number(3.0)).unwrap();
+        assert_eq!(global.borrow().get("a"), Some(Value::Number(3.0)));
+        assert_eq!(local.get_at(0, "a"), Some(Value::Number(2.0)));
+    }
 }
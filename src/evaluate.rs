@@ -1,84 +1,319 @@
-use crate::env::Env;
+use crate::class::Instance;
 use crate::expr::{Expr, Value};
+use crate::interpreter::Interpreter;
+use crate::number::{integer, negate, shift, to_bigint, to_float, Operands};
 use crate::parser_error::Error;
+use crate::span::Span;
+use crate::token::Token;
 use crate::token_type::TokenType;
+use num_bigint::BigInt;
+use std::rc::Rc;
 
-fn is_truthy(value: &Value) -> bool {
+pub fn is_truthy(value: &Value) -> bool {
     !matches!(value, Value::Nil | Value::Boolean(false))
 }
 
-fn numeric(v: &Value) -> Result<f64, Error> {
-    match v {
-        Value::Number(num) => Ok(*num),
-        _ => Err(Error::EvalError("Operand must be a number".to_string())),
+/// Promotes the operands of an arithmetic or comparison operator to a common
+/// numeric type, pointing any type error at the operand that isn't a number.
+fn numeric_operands(
+    left: &Value,
+    left_span: Span,
+    right: &Value,
+    right_span: Span,
+) -> Result<Operands, Error> {
+    Operands::of(left, right).ok_or_else(|| {
+        let span = if to_float(left).is_none() {
+            left_span
+        } else {
+            right_span
+        };
+        Error::EvalError("Operand must be a number".to_string(), span, None)
+    })
+}
+
+/// Reads an operand of a bitwise operator, which must be an integer.
+fn integer_operand(value: &Value, span: Span) -> Result<BigInt, Error> {
+    to_bigint(value).ok_or_else(|| {
+        Error::EvalError(
+            "Bitwise operators need integer operands.".to_string(),
+            span,
+            None,
+        )
+    })
+}
+
+fn check_arity(expected: usize, got: usize, span: Span) -> Result<(), Error> {
+    if expected != got {
+        return Err(Error::EvalError(
+            format!("Expected {} arguments but got {}.", expected, got),
+            span,
+            None,
+        ));
     }
+    Ok(())
 }
 
-fn is_equal(right: &Value, left: &Value) -> Result<bool, Error> {
-    match (right, left) {
-        (Value::Number(num1), Value::Number(num2)) => Ok(num1 == num2),
-        (Value::String(str1), Value::String(str2)) => Ok(str1 == str2),
-        (Value::Boolean(bool1), Value::Boolean(bool2)) => Ok(bool1 == bool2),
-        (Value::Nil, Value::Nil) => Ok(true),
-        _ => Err(Error::EvalError(
-            "Operands must be of same type".to_string(),
-        )),
+/// Equality is defined between any two values: values of different types are
+/// never equal, except that numbers of any kind compare by numeric value.
+/// Numbers follow IEEE 754, so `NaN` is unequal even to itself.
+/// Strings compare by content, while functions, classes and instances are
+/// only equal to the very same object.
+fn is_equal(left: &Value, right: &Value) -> bool {
+    if let Some(operands) = Operands::of(left, right) {
+        return operands.compare() == Some(std::cmp::Ordering::Equal);
     }
+    left == right
+}
+
+pub fn evaluate(expr: &Expr, interpreter: &mut Interpreter) -> Result<Value, Error> {
+    interpreter.enter(|| expr.span())?;
+    let value = evaluate_expr(expr, interpreter);
+    interpreter.exit();
+    value
 }
 
-pub fn evaluate<'a>(expr: Expr<'a>, env: &'a Env) -> Result<Value, Error> {
+fn evaluate_expr(expr: &Expr, interpreter: &mut Interpreter) -> Result<Value, Error> {
     match expr {
-        Expr::Literal(value) => Ok(value),
-        Expr::Grouping(expr) => evaluate(*expr, env),
+        Expr::Assign(name_token, expr_value, depth) => {
+            let value = evaluate(expr_value, interpreter)?;
+            interpreter.assign_variable(name_token, depth, value.clone())?;
+            Ok(value)
+        }
+        Expr::Literal(value, _) => Ok(value.clone()),
+        Expr::Grouping(expr, _) => evaluate(expr, interpreter),
+        Expr::Interpolation(parts, _) => {
+            let mut string = String::new();
+            for part in parts {
+                string.push_str(&evaluate(part, interpreter)?.to_string());
+            }
+            Ok(Value::String(string))
+        }
+        Expr::Call(expr_callee, _, expr_arguments) => {
+            call(expr, expr_callee, expr_arguments, interpreter)
+        }
+        Expr::Get(expr_object, name) => match evaluate(expr_object, interpreter)? {
+            Value::Instance(instance) => Instance::get(&instance, &name.lexeme).ok_or_else(|| {
+                Error::EvalError(
+                    format!("Undefined property '{}'.", name.lexeme),
+                    name.span,
+                    None,
+                )
+            }),
+            _ => Err(Error::EvalError(
+                "Only instances have properties.".to_string(),
+                expr_object.span(),
+                None,
+            )),
+        },
+        Expr::Set(expr_object, name, expr_value) => match evaluate(expr_object, interpreter)? {
+            Value::Instance(instance) => {
+                let value = evaluate(expr_value, interpreter)?;
+                instance
+                    .borrow_mut()
+                    .set(name.lexeme.clone(), value.clone());
+                Ok(value)
+            }
+            _ => Err(Error::EvalError(
+                "Only instances have fields.".to_string(),
+                expr_object.span(),
+                None,
+            )),
+        },
+        Expr::Super(keyword, method, depth) => {
+            let superclass = interpreter.look_up_variable(keyword, depth)?;
+            // `this` is always bound in the scope just inside the one holding `super`.
+            let instance = depth
+                .get()
+                .and_then(|depth| interpreter.env.borrow().get_at(depth - 1, "this"));
+            match (superclass, instance) {
+                (Value::Class(superclass), Some(instance)) => {
+                    let method = superclass.find_method(&method.lexeme).ok_or_else(|| {
+                        Error::EvalError(
+                            format!("Undefined property '{}'.", method.lexeme),
+                            method.span,
+                            None,
+                        )
+                    })?;
+                    Ok(Value::Function(Rc::new(method.bind(instance))))
+                }
+                _ => Err(Error::EvalError(
+                    "Can't use 'super' outside of a subclass.".to_string(),
+                    keyword.span,
+                    None,
+                )),
+            }
+        }
+        Expr::This(keyword, depth) => interpreter.look_up_variable(keyword, depth),
+        Expr::Logical(expr_left, token, expr_right) => {
+            let left = evaluate(expr_left, interpreter)?;
+            // Short-circuit, yielding whichever operand decided the result.
+            match token.token_type {
+                TokenType::Or if is_truthy(&left) => Ok(left),
+                TokenType::And if !is_truthy(&left) => Ok(left),
+                TokenType::Or | TokenType::And => evaluate(expr_right, interpreter),
+                _ => Err(Error::EvalError(
+                    "Unknown operator".to_string(),
+                    token.span,
+                    None,
+                )),
+            }
+        }
         Expr::Unary(token, expr_right) => {
-            let right = evaluate(*expr_right, env)?;
+            let right = evaluate(expr_right, interpreter)?;
             match token.token_type {
-                TokenType::Minus => match right {
-                    Value::Number(num) => Ok(Value::Number(-num)),
-                    _ => Err(Error::EvalError("Operand must be a number".to_string())),
-                },
+                TokenType::Minus => negate(&right).ok_or_else(|| {
+                    Error::EvalError(
+                        "Operand must be a number".to_string(),
+                        expr_right.span(),
+                        None,
+                    )
+                }),
                 TokenType::Bang => Ok(Value::Boolean(!is_truthy(&right))),
-                _ => Err(Error::EvalError("Unknown operator".to_string())),
+                TokenType::Tilde => Ok(integer(!integer_operand(&right, expr_right.span())?)),
+                _ => Err(Error::EvalError(
+                    "Unknown operator".to_string(),
+                    token.span,
+                    None,
+                )),
             }
         }
         Expr::Binary(expr_left, token, expr_right) => {
-            let left = evaluate(*expr_left, env)?;
-            let right = evaluate(*expr_right, env)?;
+            let left = evaluate(expr_left, interpreter)?;
+            let right = evaluate(expr_right, interpreter)?;
+            binary(token, left, expr_left, right, expr_right)
+        }
+        Expr::Variable(name_token, depth) => interpreter.look_up_variable(name_token, depth),
+    }
+}
+
+/// Evaluates a call expression's callee and arguments, then calls it.
+fn call(
+    expr: &Expr,
+    expr_callee: &Expr,
+    expr_arguments: &[Expr],
+    interpreter: &mut Interpreter,
+) -> Result<Value, Error> {
+    let callee = evaluate(expr_callee, interpreter)?;
+    let arguments = expr_arguments
+        .iter()
+        .map(|argument| evaluate(argument, interpreter))
+        .collect::<Result<Vec<_>, _>>()?;
+    match callee {
+        Value::Function(function) => {
+            check_arity(function.arity(), arguments.len(), expr.span())?;
+            interpreter.call_function(&function, arguments)
+        }
+        Value::Native(native) => {
+            check_arity(native.arity, arguments.len(), expr.span())?;
+            (native.function)(&arguments, expr.span())
+        }
+        Value::Class(class) => {
+            check_arity(class.arity(), arguments.len(), expr.span())?;
+            interpreter.instantiate(&class, arguments)
+        }
+        _ => Err(Error::EvalError(
+            "Can only call functions and classes.".to_string(),
+            expr_callee.span(),
+            None,
+        )),
+    }
+}
+
+/// Applies a binary operator to operands that have already been evaluated.
+/// Kept out of `evaluate_expr` so that its temporaries don't weigh down every
+/// level of recursion.
+fn binary(
+    token: &Token,
+    left: Value,
+    expr_left: &Expr,
+    right: Value,
+    expr_right: &Expr,
+) -> Result<Value, Error> {
+    let span = expr_left.span().to(expr_right.span());
+    // Type errors point at whichever operand was the wrong type.
+    let operands = || numeric_operands(&left, expr_left.span(), &right, expr_right.span());
+    let division_by_zero = || Error::EvalError("Division by zero.".to_string(), span, None);
+    match token.token_type {
+        TokenType::Plus => match (&left, &right) {
+            (Value::String(left), Value::String(right)) => {
+                Ok(Value::String(format!("{}{}", left, right)))
+            }
+            (Value::String(_), _) | (_, Value::String(_)) => Err(Error::EvalError(
+                "Can only concatenate a string with another string; use \"${...}\" to convert other values.".to_string(),
+                span,
+                None,
+            )),
+            _ => Ok(operands()?.add()),
+        },
+        TokenType::Minus => Ok(operands()?.sub()),
+        TokenType::Star => Ok(operands()?.mul()),
+        TokenType::Slash | TokenType::SlashSlash | TokenType::Percent => {
+            let operands = operands()?;
+            if operands.divides_by_zero() {
+                return Err(division_by_zero());
+            }
             match token.token_type {
-                TokenType::Plus => Ok(Value::Number(numeric(&left)? + numeric(&right)?)),
-                TokenType::Minus => Ok(Value::Number(numeric(&left)? - numeric(&right)?)),
-                TokenType::Star => Ok(Value::Number(numeric(&left)? * numeric(&right)?)),
-                TokenType::Slash => Ok(Value::Number(numeric(&left)? / numeric(&right)?)),
-                TokenType::Greater => Ok(Value::Boolean(numeric(&left)? > numeric(&right)?)),
-                TokenType::GreaterEqual => Ok(Value::Boolean(numeric(&left)? >= numeric(&right)?)),
-                TokenType::Less => Ok(Value::Boolean(numeric(&left)? < numeric(&right)?)),
-                TokenType::LessEqual => Ok(Value::Boolean(numeric(&left)? <= numeric(&right)?)),
-                TokenType::BangEqual => Ok(Value::Boolean(!is_equal(&left, &right)?)),
-                TokenType::EqualEqual => Ok(Value::Boolean(is_equal(&left, &right)?)),
-                _ => Err(Error::EvalError("Unknown operator".to_string())),
+                TokenType::Slash => Ok(operands.div()),
+                TokenType::SlashSlash => Ok(operands.floor_div()),
+                _ => Ok(operands.rem()),
             }
         }
-        Expr::Variable(name_token_ref) => {
-            let name = &name_token_ref.lexeme;
-            if let Some(value_ref) = env.get(name) {
-                // TODO fix clone
-                Ok(value_ref.clone())
-            } else {
-                Err(Error::EvalError(format!("Undefined variable '{}'", name)))
+        TokenType::StarStar => operands()?
+            .pow()
+            .map_err(|message| Error::EvalError(message, span, None)),
+        TokenType::Ampersand
+        | TokenType::Pipe
+        | TokenType::Caret
+        | TokenType::LessLess
+        | TokenType::GreaterGreater => {
+            let left = integer_operand(&left, expr_left.span())?;
+            let right = integer_operand(&right, expr_right.span())?;
+            match token.token_type {
+                TokenType::Ampersand => Ok(integer(left & right)),
+                TokenType::Pipe => Ok(integer(left | right)),
+                TokenType::Caret => Ok(integer(left ^ right)),
+                _ => shift(left, &right, token.token_type == TokenType::LessLess)
+                    .map_err(|message| Error::EvalError(message, span, None)),
             }
         }
+        TokenType::Greater
+        | TokenType::GreaterEqual
+        | TokenType::Less
+        | TokenType::LessEqual => {
+            let ordering = match (&left, &right) {
+                (Value::String(left), Value::String(right)) => left.partial_cmp(right),
+                (Value::String(_), _) | (_, Value::String(_)) => {
+                    return Err(Error::EvalError(
+                        "Can only compare a string with another string.".to_string(),
+                        span,
+                        None,
+                    ))
+                }
+                _ => operands()?.compare(),
+            };
+            // NaN is unordered, so every comparison with it is false.
+            let result = ordering.is_some_and(|ordering| match token.token_type {
+                TokenType::Greater => ordering.is_gt(),
+                TokenType::GreaterEqual => ordering.is_ge(),
+                TokenType::Less => ordering.is_lt(),
+                _ => ordering.is_le(),
+            });
+            Ok(Value::Boolean(result))
+        }
+        TokenType::BangEqual => Ok(Value::Boolean(!is_equal(&left, &right))),
+        TokenType::EqualEqual => Ok(Value::Boolean(is_equal(&left, &right))),
+        _ => Err(Error::EvalError("Unknown operator".to_string(), token.span, None)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::token::Token;
 
     #[test]
     fn test_evaluate_literal() {
-        let expr = Expr::Literal(Value::Number(42.0));
-        let result = evaluate(expr, &Env::new()).unwrap();
+        let expr = Expr::Literal(Value::Number(42.0), Span::default());
+        let result = evaluate(&expr, &mut Interpreter::new()).unwrap();
         assert_eq!(result, Value::Number(42.0));
     }
 
@@ -88,10 +323,63 @@ mod tests {
             token_type: TokenType::Minus,
             lexeme: "-".to_string(),
             literal: "".to_string(),
-            line: 1,
+            span: Span::default(),
         };
-        let expr = Expr::Unary(&token, Box::new(Expr::Literal(Value::Number(42.0))));
-        let result = evaluate(expr, &Env::new()).unwrap();
+        let expr = Expr::Unary(
+            token,
+            Box::new(Expr::Literal(Value::Number(42.0), Span::default())),
+        );
+        let result = evaluate(&expr, &mut Interpreter::new()).unwrap();
         assert_eq!(result, Value::Number(-42.0));
     }
+
+    #[test]
+    fn test_evaluate_logical_short_circuits() {
+        let or_token = Token {
+            token_type: TokenType::Or,
+            lexeme: "or".to_string(),
+            literal: "".to_string(),
+            span: Span::default(),
+        };
+        let and_token = Token {
+            token_type: TokenType::And,
+            lexeme: "and".to_string(),
+            literal: "".to_string(),
+            span: Span::default(),
+        };
+        let undefined = Token {
+            token_type: TokenType::Identifier,
+            lexeme: "undefined".to_string(),
+            literal: "".to_string(),
+            span: Span::default(),
+        };
+
+        // The right operand would fail if it were evaluated.
+        let expr = Expr::Logical(
+            Box::new(Expr::Literal(
+                Value::String("hi".to_string()),
+                Span::default(),
+            )),
+            or_token.clone(),
+            Box::new(Expr::Variable(undefined.clone(), Default::default())),
+        );
+        let result = evaluate(&expr, &mut Interpreter::new()).unwrap();
+        assert_eq!(result, Value::String("hi".to_string()));
+
+        let expr = Expr::Logical(
+            Box::new(Expr::Literal(Value::Nil, Span::default())),
+            and_token,
+            Box::new(Expr::Variable(undefined, Default::default())),
+        );
+        let result = evaluate(&expr, &mut Interpreter::new()).unwrap();
+        assert_eq!(result, Value::Nil);
+
+        let expr = Expr::Logical(
+            Box::new(Expr::Literal(Value::Boolean(false), Span::default())),
+            or_token,
+            Box::new(Expr::Literal(Value::Number(1.0), Span::default())),
+        );
+        let result = evaluate(&expr, &mut Interpreter::new()).unwrap();
+        assert_eq!(result, Value::Number(1.0));
+    }
 }
@@ -1,19 +1,118 @@
+use crate::class::{Class, Instance};
+use crate::function::Function;
+use crate::native::NativeFunction;
+use crate::number::format_rational;
+use crate::span::Span;
 use crate::token::Token;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// How many scopes out from a reference its variable was declared, filled in
+/// by the resolver. `None` means the variable is global.
+pub type Depth = Cell<Option<usize>>;
 
 #[derive(Debug)]
-pub enum Expr<'a> {
-    Binary(Box<Expr<'a>>, &'a Token, Box<Expr<'a>>),
-    Grouping(Box<Expr<'a>>),
-    Literal(Value),
-    Unary(&'a Token, Box<Expr<'a>>),
-    Variable(&'a Token),
+pub enum Expr {
+    Assign(Token, Box<Expr>, Depth),
+    Binary(Box<Expr>, Token, Box<Expr>),
+    // callee, closing paren, arguments
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
+    Grouping(Box<Expr>, Span),
+    // literal text and embedded expressions of an interpolated string, in order
+    Interpolation(Vec<Expr>, Span),
+    Literal(Value, Span),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Set(Box<Expr>, Token, Box<Expr>),
+    // keyword, method
+    Super(Token, Token, Depth),
+    This(Token, Depth),
+    Unary(Token, Box<Expr>),
+    Variable(Token, Depth),
+}
+impl Expr {
+    /// The region of source this expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(name, value, _) => name.span.to(value.span()),
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                left.span().to(right.span())
+            }
+            Expr::Call(callee, paren, _) => callee.span().to(paren.span),
+            Expr::Get(object, name) => object.span().to(name.span),
+            Expr::Grouping(_, span) | Expr::Interpolation(_, span) | Expr::Literal(_, span) => {
+                *span
+            }
+            Expr::Set(object, _, value) => object.span().to(value.span()),
+            Expr::Super(keyword, method, _) => keyword.span.to(method.span),
+            Expr::This(keyword, _) | Expr::Variable(keyword, _) => keyword.span,
+            Expr::Unary(operator, right) => operator.span.to(right.span()),
+        }
+    }
 }
-#[derive(Debug, PartialEq, Clone)]
+
+#[derive(Debug, Clone)]
 pub enum Value {
+    Int(i64),
+    // Integers too large for `Int`; arithmetic moves between the two as needed.
+    BigInt(BigInt),
+    // Exact decimals and fractions, written `0.1d`.
+    Rational(BigRational),
     Number(f64),
     String(String),
     Boolean(bool),
     Nil,
+    Function(Rc<Function>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+/// Data compares by content, while functions, classes and instances are only
+/// equal to the very same object. Numbers of different types are unequal
+/// here; the language's `==` compares them by value.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(int1), Value::Int(int2)) => int1 == int2,
+            (Value::BigInt(int1), Value::BigInt(int2)) => int1 == int2,
+            (Value::Rational(rational1), Value::Rational(rational2)) => rational1 == rational2,
+            (Value::Number(num1), Value::Number(num2)) => num1 == num2,
+            (Value::String(str1), Value::String(str2)) => str1 == str2,
+            (Value::Boolean(bool1), Value::Boolean(bool2)) => bool1 == bool2,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(fn1), Value::Function(fn2)) => Rc::ptr_eq(fn1, fn2),
+            (Value::Native(fn1), Value::Native(fn2)) => Rc::ptr_eq(fn1, fn2),
+            (Value::Class(class1), Value::Class(class2)) => Rc::ptr_eq(class1, class2),
+            (Value::Instance(instance1), Value::Instance(instance2)) => {
+                Rc::ptr_eq(instance1, instance2)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How values appear in `print` output and interpolated strings.
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            Value::BigInt(int) => write!(f, "{}", int),
+            Value::Rational(rational) => write!(f, "{}", format_rational(rational)),
+            // Floats always show a fraction or exponent so they can't be
+            // mistaken for integers, like `3.0` and `1e300`.
+            Value::Number(num) => write!(f, "{:?}", num),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Nil => write!(f, "nil"),
+            Value::Function(function) => write!(f, "{:?}", function),
+            Value::Native(native) => write!(f, "{:?}", native),
+            Value::Class(class) => write!(f, "{:?}", class),
+            Value::Instance(instance) => write!(f, "{:?}", instance.borrow()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -27,4 +126,18 @@ mod tests {
         assert_ne!(Value::Number(42.0), Value::Number(43.0));
         assert_ne!(Value::Boolean(true), Value::Boolean(false));
     }
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(Value::Int(-7).to_string(), "-7");
+        assert_eq!(Value::Number(3.0).to_string(), "3.0");
+        assert_eq!(Value::Number(-0.5).to_string(), "-0.5");
+        assert_eq!(Value::Number(123456.0).to_string(), "123456.0");
+        assert_eq!(Value::Number(1e21).to_string(), "1e21");
+        assert_eq!(Value::Number(1e300).to_string(), "1e300");
+        assert_eq!(Value::Number(1.5e-7).to_string(), "1.5e-7");
+        assert_eq!(Value::String("hi".to_string()).to_string(), "hi");
+        assert_eq!(Value::Boolean(false).to_string(), "false");
+        assert_eq!(Value::Nil.to_string(), "nil");
+    }
 }
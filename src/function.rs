@@ -0,0 +1,50 @@
+use crate::env::Env;
+use crate::expr::Value;
+use crate::stmt::FunctionDecl;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A user-defined function created by evaluating a `fun` declaration.
+pub struct Function {
+    pub declaration: Rc<FunctionDecl>,
+    /// The scope the function was declared in, kept alive for as long as the
+    /// function is.
+    pub closure: Rc<RefCell<Env>>,
+    /// Initializers always return `this`, whatever their body returns.
+    pub is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Env>>,
+        is_initializer: bool,
+    ) -> Self {
+        Function {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method with `this` bound to `instance`.
+    pub fn bind(&self, instance: Value) -> Function {
+        let mut env = Env::with_enclosing(self.closure.clone());
+        env.define("this", instance);
+        Function::new(
+            self.declaration.clone(),
+            Rc::new(RefCell::new(env)),
+            self.is_initializer,
+        )
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
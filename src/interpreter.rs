@@ -1,11 +1,38 @@
+use crate::class::{Class, Instance};
 use crate::env::Env;
-use crate::evaluate::evaluate;
-use crate::expr::Value;
+use crate::evaluate::{evaluate, is_truthy};
+use crate::expr::{Depth, Value};
+use crate::function::Function;
+use crate::native::define_natives;
 use crate::parser_error::Error;
+use crate::span::Span;
 use crate::stmt::Stmt;
+use crate::token::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// How control leaves a statement once it has finished executing.
+#[derive(Debug, PartialEq)]
+pub enum ControlFlow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+const UNDEFINED_VARIABLE_HELP: &str = "declare the variable with `var` before using it";
+
+/// How deeply statements and expressions may nest while running, counting
+/// through every call, before the interpreter reports a stack overflow rather
+/// than exhausting the native stack.
+const MAX_DEPTH: usize = 10_000;
 
 pub struct Interpreter {
-    env: Env,
+    globals: Rc<RefCell<Env>>,
+    pub(crate) env: Rc<RefCell<Env>>,
+    // How many statements and expressions are being evaluated right now.
+    depth: usize,
 }
 
 impl Default for Interpreter {
@@ -15,40 +42,884 @@ impl Default for Interpreter {
 }
 
 impl Interpreter {
+    /// The native stack size to run the interpreter on. Reaching `MAX_DEPTH`
+    /// takes far more than the default stack, especially in debug builds.
+    pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
     pub fn new() -> Self {
-        Interpreter { env: Env::new() }
+        let mut globals = Env::new();
+        define_natives(&mut globals);
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            env: globals.clone(),
+            globals,
+            depth: 0,
+        }
+    }
+
+    /// Counts one more level of nesting, failing once it passes `MAX_DEPTH`.
+    /// Every successful call must be paired with a call to `exit`.
+    pub(crate) fn enter(&mut self, span: impl FnOnce() -> Span) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::EvalError(
+                "Stack overflow.".to_string(),
+                span(),
+                None,
+            ));
+        }
+        self.depth += 1;
+        Ok(())
     }
 
-    pub fn evaluate_stmt(&mut self, stmt: Stmt) -> Result<(), Error> {
+    pub(crate) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub fn evaluate_stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow, Error> {
+        self.enter(|| stmt.span())?;
+        let flow = self.execute(stmt);
+        self.exit();
+        flow
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<ControlFlow, Error> {
         match stmt {
-            Stmt::ExprStmt(expr) => {
+            Stmt::ExprStmt(expr, _) => {
                 // Evaluate the expression but don't print the result
-                evaluate(*expr, &self.env)?;
-                Ok(())
-            }
-            Stmt::PrintStmt(expr) => {
-                // Print the result of evaluating the expression
-                println!("{:?}", evaluate(*expr, &self.env)?);
-                Ok(())
-            }
-            Stmt::VarStmt(name_token, initializer) => {
-                if let Some(initializer) = initializer {
-                    let value = evaluate(*initializer, &self.env)?;
-                    self.env.define(name_token.lexeme.clone(), value);
+                evaluate(expr, self)?;
+                Ok(ControlFlow::Normal)
+            }
+            Stmt::PrintStmt(expr, _) => {
+                println!("{}", evaluate(expr, self)?);
+                Ok(ControlFlow::Normal)
+            }
+            Stmt::VarStmt(name_token, initializer, _) => {
+                let value = match initializer {
+                    Some(initializer) => evaluate(initializer, self)?,
+                    None => Value::Nil,
+                };
+                self.env
+                    .borrow_mut()
+                    .define(name_token.lexeme.clone(), value);
+                Ok(ControlFlow::Normal)
+            }
+            Stmt::Block(statements, _) => {
+                let env = Env::with_enclosing(self.env.clone());
+                self.execute_block(statements, env)
+            }
+            Stmt::If(condition, then_branch, else_branch, _) => {
+                if is_truthy(&evaluate(condition, self)?) {
+                    self.evaluate_stmt(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.evaluate_stmt(else_branch)
                 } else {
-                    self.env.define(name_token.lexeme.clone(), Value::Nil);
+                    Ok(ControlFlow::Normal)
                 }
-                Ok(())
             }
+            Stmt::While(condition, body, _) => {
+                while is_truthy(&evaluate(condition, self)?) {
+                    match self.evaluate_stmt(body)? {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
+                Ok(ControlFlow::Normal)
+            }
+            Stmt::For(initializer, condition, increment, body, _) => {
+                // The initializer gets its own scope so loop variables don't leak.
+                let env = Env::with_enclosing(self.env.clone());
+                self.in_scope(env, |interpreter| {
+                    if let Some(initializer) = initializer {
+                        interpreter.evaluate_stmt(initializer)?;
+                    }
+                    loop {
+                        if let Some(condition) = condition {
+                            if !is_truthy(&evaluate(condition, interpreter)?) {
+                                break;
+                            }
+                        }
+                        match interpreter.evaluate_stmt(body)? {
+                            ControlFlow::Break => break,
+                            ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+                            ControlFlow::Normal | ControlFlow::Continue => {}
+                        }
+                        // `continue` still runs the increment before the next iteration.
+                        if let Some(increment) = increment {
+                            evaluate(increment, interpreter)?;
+                        }
+                    }
+                    Ok(ControlFlow::Normal)
+                })
+            }
+            Stmt::Break(_) => Ok(ControlFlow::Break),
+            Stmt::Continue(_) => Ok(ControlFlow::Continue),
+            Stmt::Function(declaration) => {
+                let function = Function::new(declaration.clone(), self.env.clone(), false);
+                self.env.borrow_mut().define(
+                    declaration.name.lexeme.clone(),
+                    Value::Function(Rc::new(function)),
+                );
+                Ok(ControlFlow::Normal)
+            }
+            Stmt::Return(_, value) => {
+                let value = match value {
+                    Some(value) => evaluate(value, self)?,
+                    None => Value::Nil,
+                };
+                Ok(ControlFlow::Return(value))
+            }
+            Stmt::Class(name, superclass, declarations) => {
+                let superclass = match superclass {
+                    Some(superclass) => match evaluate(superclass, self)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(Error::EvalError(
+                                "Superclass must be a class.".to_string(),
+                                superclass.span(),
+                                None,
+                            ))
+                        }
+                    },
+                    None => None,
+                };
+                // Methods of a subclass close over a scope that binds `super`.
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut env = Env::with_enclosing(self.env.clone());
+                        env.define("super", Value::Class(superclass.clone()));
+                        Rc::new(RefCell::new(env))
+                    }
+                    None => self.env.clone(),
+                };
+                let methods: HashMap<_, _> = declarations
+                    .iter()
+                    .map(|declaration| {
+                        let is_initializer = declaration.name.lexeme == "init";
+                        let method =
+                            Function::new(declaration.clone(), closure.clone(), is_initializer);
+                        (declaration.name.lexeme.clone(), Rc::new(method))
+                    })
+                    .collect();
+                let class = Class::new(name.lexeme.clone(), superclass, methods);
+                self.env
+                    .borrow_mut()
+                    .define(name.lexeme.clone(), Value::Class(Rc::new(class)));
+                Ok(ControlFlow::Normal)
+            }
+        }
+    }
+
+    /// Reads a variable using the scope depth computed by the resolver.
+    pub(crate) fn look_up_variable(&self, name: &Token, depth: &Depth) -> Result<Value, Error> {
+        let value = match depth.get() {
+            Some(depth) => self.env.borrow().get_at(depth, &name.lexeme),
+            None => self.globals.borrow().get(&name.lexeme),
+        };
+        value.ok_or_else(|| {
+            Error::EvalError(
+                format!("Undefined variable '{}'", name.lexeme),
+                name.span,
+                None,
+            )
+            .with_help(UNDEFINED_VARIABLE_HELP)
+        })
+    }
+
+    /// Updates a variable using the scope depth computed by the resolver.
+    pub(crate) fn assign_variable(
+        &self,
+        name: &Token,
+        depth: &Depth,
+        value: Value,
+    ) -> Result<(), Error> {
+        match depth.get() {
+            Some(depth) => self.env.borrow_mut().assign_at(depth, &name.lexeme, value),
+            None => self.globals.borrow_mut().assign(&name.lexeme, value),
+        }
+        .map_err(|message| {
+            Error::EvalError(message, name.span, None).with_help(UNDEFINED_VARIABLE_HELP)
+        })
+    }
+
+    /// Runs `function` with `arguments` bound to its parameters and returns
+    /// the value it returned, or `nil` if it ran off the end of its body.
+    pub(crate) fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let mut env = Env::with_enclosing(function.closure.clone());
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            env.define(param.lexeme.clone(), argument);
+        }
+        let flow = self.execute_block(&function.declaration.body, env)?;
+        if function.is_initializer {
+            return Ok(function.closure.borrow().get("this").unwrap_or(Value::Nil));
+        }
+        match flow {
+            ControlFlow::Return(value) => Ok(value),
+            _ => Ok(Value::Nil),
+        }
+    }
+
+    /// Creates a new instance of `class`, running its initializer if it has one.
+    pub(crate) fn instantiate(
+        &mut self,
+        class: &Rc<Class>,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(class.clone()))));
+        if let Some(initializer) = class.find_method("init") {
+            self.call_function(&initializer.bind(instance.clone()), arguments)?;
         }
+        Ok(instance)
     }
 
-    pub fn interpret(&mut self, statements: Vec<Stmt>) {
-        for stmt in statements {
-            let eval_result = self.evaluate_stmt(stmt);
-            if let Err(error) = eval_result {
-                println!("error: {:?}", error);
+    fn execute_block(&mut self, statements: &[Stmt], env: Env) -> Result<ControlFlow, Error> {
+        self.in_scope(env, |interpreter| {
+            for stmt in statements {
+                let flow = interpreter.evaluate_stmt(stmt)?;
+                if flow != ControlFlow::Normal {
+                    return Ok(flow);
+                }
             }
+            Ok(ControlFlow::Normal)
+        })
+    }
+
+    fn in_scope<T>(
+        &mut self,
+        env: Env,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let previous = std::mem::replace(&mut self.env, Rc::new(RefCell::new(env)));
+        let result = f(self);
+        // Restore the previous scope even if the scope failed part way through.
+        self.env = previous;
+        result
+    }
+
+    /// Runs each top-level statement in turn. A runtime error abandons only
+    /// the statement that raised it; every error is returned for reporting.
+    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Vec<Error> {
+        statements
+            .iter()
+            .filter_map(|stmt| self.evaluate_stmt(stmt).err())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Error> {
+        let mut scanner = Scanner::new(source);
+        scanner
+            .scan_tokens()
+            .map_err(|mut errors| errors.remove(0))?;
+        let statements = Parser::new(&scanner.tokens)
+            .parse()
+            .map_err(|mut errors| errors.remove(0))?;
+        Resolver::new()
+            .resolve(&statements)
+            .map_err(|mut errors| errors.remove(0))?;
+        Ok(statements)
+    }
+
+    fn run(source: &str) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        let errors = interpreter.interpret(parse(source).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        interpreter
+    }
+
+    /// Runs `source`, stopping at the first runtime error.
+    fn execute(source: &str) -> Result<(), Error> {
+        let mut interpreter = Interpreter::new();
+        parse(source)?
+            .iter()
+            .try_for_each(|stmt| interpreter.evaluate_stmt(stmt).map(|_| ()))
+    }
+
+    #[test]
+    fn test_block_locals_do_not_leak() {
+        let interpreter = run("var a = 1; { var b = 2; }");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(1)));
+        assert_eq!(interpreter.env.borrow().get("b"), None);
+    }
+
+    #[test]
+    fn test_block_shadowing() {
+        let interpreter = run("var a = 1; { var a = 2; var b = a; { var a = 3; } }");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(1)));
+    }
+
+    #[test]
+    fn test_assignment_updates_enclosing_scope() {
+        let interpreter = run("var a = 1; var b; { a = b = 2; }");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(2)));
+        assert_eq!(interpreter.env.borrow().get("b"), Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_assignment_to_undeclared_variable() {
+        let result = execute("a = 1;");
+        assert!(matches!(result, Err(error) if error.message() == "Undefined variable 'a'"));
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        assert!(parse("1 = 2;").is_err());
+    }
+
+    #[test]
+    fn test_if_else() {
+        let interpreter =
+            run("var a; var b; if (1 < 2) a = 1; else a = 2; if (nil) b = 1; else b = 2;");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(1)));
+        assert_eq!(interpreter.env.borrow().get("b"), Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_nearest_if() {
+        let interpreter = run("var a = 0; if (true) if (false) a = 1; else a = 2;");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(2)));
+        let interpreter = run("var a = 0; if (false) if (true) a = 1; else a = 2;");
+        assert_eq!(interpreter.env.borrow().get("a"), Some(Value::Int(0)));
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let interpreter =
+            run("var i = 0; var sum = 0; while (i < 5) { sum = sum + i; i = i + 1; }");
+        assert_eq!(interpreter.env.borrow().get("sum"), Some(Value::Int(10)));
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let interpreter = run("var sum = 0; for (var i = 0; i < 5; i = i + 1) sum = sum + i;");
+        assert_eq!(interpreter.env.borrow().get("sum"), Some(Value::Int(10)));
+        assert_eq!(interpreter.env.borrow().get("i"), None);
+    }
+
+    #[test]
+    fn test_for_loop_without_clauses() {
+        let interpreter = run("var i = 0; for (; i < 3;) i = i + 1;");
+        assert_eq!(interpreter.env.borrow().get("i"), Some(Value::Int(3)));
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let interpreter = run(
+            "var sum = 0; for (var i = 0; i < 10; i = i + 1) { if (i == 2) continue; if (i == 5) break; sum = sum + i; }",
+        );
+        assert_eq!(interpreter.env.borrow().get("sum"), Some(Value::Int(8)));
+
+        let interpreter = run("var i = 0; while (true) { i = i + 1; { if (i == 3) break; } }");
+        assert_eq!(interpreter.env.borrow().get("i"), Some(Value::Int(3)));
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        for source in ["break;", "{ continue; }", "if (true) break;"] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_or_binds_looser_than_and() {
+        let interpreter = run("var a = true or false and false; var b = nil or 0 and \"x\";");
+        assert_eq!(
+            interpreter.env.borrow().get("a"),
+            Some(Value::Boolean(true))
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("b"),
+            Some(Value::String("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_function_call_and_return() {
+        let interpreter = run(
+            "fun add(a, b) { return a + b; } fun noop() {} var sum = add(1, 2); var nothing = noop();",
+        );
+        assert_eq!(interpreter.env.borrow().get("sum"), Some(Value::Int(3)));
+        assert_eq!(interpreter.env.borrow().get("nothing"), Some(Value::Nil));
+    }
+
+    #[test]
+    fn test_return_unwinds_loops_and_recursion() {
+        let interpreter = run(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } \
+             fun first(limit) { for (var i = 0; i < limit; i = i + 1) { while (true) { return i + 10; } } } \
+             var f = fib(10); var g = first(5);",
+        );
+        assert_eq!(interpreter.env.borrow().get("f"), Some(Value::Int(55)));
+        assert_eq!(interpreter.env.borrow().get("g"), Some(Value::Int(10)));
+    }
+
+    #[test]
+    fn test_call_arity_and_callee_errors() {
+        for (source, expected) in [
+            ("fun f(a) {} f(1, 2);", "Expected 1 arguments but got 2."),
+            (
+                "\"not a function\"();",
+                "Can only call functions and classes.",
+            ),
+        ] {
+            let result = execute(source);
+            assert!(
+                matches!(result, Err(Error::EvalError(ref message, ..)) if message == expected),
+                "{}: {:?}",
+                source,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_return_and_break_placement() {
+        for source in ["return 1;", "while (true) { fun f() { break; } }"] {
+            assert!(parse(source).is_err(), "{}", source);
         }
     }
+
+    #[test]
+    fn test_closure_counter() {
+        let interpreter = run(
+            "fun makeCounter() { var i = 0; fun count() { i = i + 1; return i; } return count; } \
+             var counter = makeCounter(); counter(); var second = counter(); \
+             var other = makeCounter(); var fresh = other();",
+        );
+        assert_eq!(interpreter.env.borrow().get("second"), Some(Value::Int(2)));
+        assert_eq!(interpreter.env.borrow().get("fresh"), Some(Value::Int(1)));
+    }
+
+    #[test]
+    fn test_closure_captures_block_scope() {
+        let interpreter = run(
+            "var get; { var hidden = \"secret\"; fun reveal() { return hidden; } get = reveal; } \
+             var value = get();",
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("value"),
+            Some(Value::String("secret".to_string()))
+        );
+        assert_eq!(interpreter.env.borrow().get("hidden"), None);
+    }
+
+    #[test]
+    fn test_class_fields_methods_and_init() {
+        let interpreter = run("class Point { init(x, y) { this.x = x; this.y = y; } \
+               sum() { return this.x + this.y; } \
+               shift(dx) { this.x = this.x + dx; return this; } } \
+             var p = Point(1, 2); var before = p.sum(); var method = p.shift; method(10); \
+             var after = p.sum(); var again = p.init(5, 0).sum();");
+        assert_eq!(interpreter.env.borrow().get("before"), Some(Value::Int(3)));
+        assert_eq!(interpreter.env.borrow().get("after"), Some(Value::Int(13)));
+        assert_eq!(interpreter.env.borrow().get("again"), Some(Value::Int(5)));
+    }
+
+    #[test]
+    fn test_this_in_nested_function() {
+        let interpreter = run(
+            "class Thing { getCallback() { fun localFunction() { return this.name; } return localFunction; } } \
+             var thing = Thing(); thing.name = \"thing\"; var name = thing.getCallback()();",
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("name"),
+            Some(Value::String("thing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_class_errors() {
+        for (source, expected) in [
+            ("class A {} A(1);", "Expected 0 arguments but got 1."),
+            ("class A {} A().missing;", "Undefined property 'missing'."),
+            ("var a = 1; a.field;", "Only instances have properties."),
+            ("var a = 1; a.field = 2;", "Only instances have fields."),
+        ] {
+            let result = execute(source);
+            assert!(
+                matches!(result, Err(Error::EvalError(ref message, ..)) if message == expected),
+                "{}: {:?}",
+                source,
+                result
+            );
+        }
+
+        for source in ["print this;", "class A { init() { return 1; } }"] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let interpreter = run(
+            "class A { init(n) { this.n = n; } name() { return \"A\"; } value() { return this.n; } } \
+             class B < A { init(n) { super.init(n * 2); } name() { return \"B\"; } \
+               describe() { return super.name(); } } \
+             class C < B { describe() { return super.describe(); } } \
+             var c = C(21); var value = c.value(); var name = c.name(); var described = c.describe();",
+        );
+        assert_eq!(interpreter.env.borrow().get("value"), Some(Value::Int(42)));
+        assert_eq!(
+            interpreter.env.borrow().get("name"),
+            Some(Value::String("B".to_string()))
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("described"),
+            Some(Value::String("A".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_inheritance_errors() {
+        let result = execute("var NotAClass = 1; class A < NotAClass {}");
+        assert!(
+            matches!(result, Err(Error::EvalError(ref message, ..)) if message == "Superclass must be a class.")
+        );
+
+        for source in [
+            "class A < A {}",
+            "class A { f() { return super.f(); } }",
+            "fun f() { super.f(); }",
+        ] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_resolved_closure_ignores_later_shadowing() {
+        let interpreter = run("var a = \"global\"; var first; var second; \
+             { fun show() { return a; } first = show(); var a = \"block\"; second = show(); }");
+        assert_eq!(
+            interpreter.env.borrow().get("first"),
+            Some(Value::String("global".to_string()))
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("second"),
+            Some(Value::String("global".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_errors_carry_spans() {
+        let error = execute("var a = 1;\nprint a + nil;").unwrap_err();
+        assert_eq!(error.message(), "Operand must be a number");
+        assert_eq!(
+            (error.span().line, error.span().column, error.span().len),
+            (2, 11, 3)
+        );
+
+        let error = execute("fun f() {}\n  f(1);").unwrap_err();
+        assert_eq!(
+            (error.span().line, error.span().column, error.span().len),
+            (2, 3, 4)
+        );
+
+        let error = parse("{ var a = 1; var a = 2; }").unwrap_err();
+        assert_eq!((error.span().line, error.span().column), (1, 18));
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        let interpreter = run(r#"
+            var name = "Ada";
+            var count = 2;
+            var greeting = "Hello ${name}, you have ${count + 1} items${"!" }";
+            var nested = "${ "<${name}>" } ${nil} ${1 < 2} ${1.5}";
+        "#);
+        assert_eq!(
+            interpreter.env.borrow().get("greeting"),
+            Some(Value::String("Hello Ada, you have 3 items!".to_string()))
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("nested"),
+            Some(Value::String("<Ada> nil true 1.5".to_string()))
+        );
+        assert!(parse(r#"var a = "${1 2}";"#).is_err());
+        let error = execute(r#"print "${-"x"}";"#).unwrap_err();
+        assert_eq!(error.message(), "Operand must be a number");
+    }
+
+    #[test]
+    fn test_string_concatenation_and_comparison() {
+        let interpreter = run(r#"
+            var joined = "foo" + "bar" + "";
+            var less = "apple" < "banana";
+            var prefix = "app" < "apple";
+            var greater = "b" >= "abc";
+            var upper = "Z" < "a";
+        "#);
+        let get = |name| interpreter.env.borrow().get(name);
+        assert_eq!(get("joined"), Some(Value::String("foobar".to_string())));
+        assert_eq!(get("less"), Some(Value::Boolean(true)));
+        assert_eq!(get("prefix"), Some(Value::Boolean(true)));
+        assert_eq!(get("greater"), Some(Value::Boolean(true)));
+        assert_eq!(get("upper"), Some(Value::Boolean(true)));
+
+        let error = execute(r#"print "n = " + 1;"#).unwrap_err();
+        assert!(error.message().starts_with("Can only concatenate a string"));
+        assert_eq!((error.span().column, error.span().len), (7, 10));
+        let error = execute(r#"print 1 < "2";"#).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Can only compare a string with another string."
+        );
+        let error = execute("print nil + 1;").unwrap_err();
+        assert_eq!(error.message(), "Operand must be a number");
+    }
+
+    #[test]
+    fn test_equality_across_types() {
+        let interpreter = run(r#"
+            var a = 1 == "1";
+            var b = nil == false;
+            var c = nil != 0;
+            var d = "x" == "x";
+            var nan = 0.0 / 0.0;
+            var e = nan == nan;
+            var f = nan != nan;
+            class P {}
+            var p = P();
+            var q = P();
+            var g = p == p;
+            var h = p == q;
+            fun fn() {}
+            var i = fn == fn;
+            var j = P == P;
+        "#);
+        let get = |name| interpreter.env.borrow().get(name);
+        let expected = [
+            ("a", false),
+            ("b", false),
+            ("c", true),
+            ("d", true),
+            ("e", false),
+            ("f", true),
+            ("g", true),
+            ("h", false),
+            ("i", true),
+            ("j", true),
+        ];
+        for (name, value) in expected {
+            assert_eq!(get(name), Some(Value::Boolean(value)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let interpreter = run(r#"
+            var big = 9007199254740993 + 0;
+            var quotient = 7 / 2;
+            var negative = -7 / 2;
+            var remainder = -7 % 3;
+            var mixed = 7 / 2.0;
+            var float_rem = 5.5 % 2;
+            var same = 1 == 1.0;
+            var less = 2 < 2.5;
+            var truncated = int(-2.9);
+            var parsed = int(" 42 ");
+            var widened = float(3);
+            var from_string = float("1e3");
+        "#);
+        let get = |name| interpreter.env.borrow().get(name);
+        assert_eq!(get("big"), Some(Value::Int(9007199254740993)));
+        assert_eq!(get("quotient"), Some(Value::Int(3)));
+        assert_eq!(get("negative"), Some(Value::Int(-3)));
+        assert_eq!(get("remainder"), Some(Value::Int(-1)));
+        assert_eq!(get("mixed"), Some(Value::Number(3.5)));
+        assert_eq!(get("float_rem"), Some(Value::Number(1.5)));
+        assert_eq!(get("same"), Some(Value::Boolean(true)));
+        assert_eq!(get("less"), Some(Value::Boolean(true)));
+        assert_eq!(get("truncated"), Some(Value::Int(-2)));
+        assert_eq!(get("parsed"), Some(Value::Int(42)));
+        assert_eq!(get("widened"), Some(Value::Number(3.0)));
+        assert_eq!(get("from_string"), Some(Value::Number(1000.0)));
+    }
+
+    #[test]
+    fn test_integer_errors() {
+        for (source, message) in [
+            ("print 1 / 0;", "Division by zero."),
+            ("print 1 % 0;", "Division by zero."),
+            ("print int(0.0 / 0);", "Can't convert NaN to an integer."),
+            (
+                "print int(\"1.5\");",
+                "Can't convert \"1.5\" to an integer.",
+            ),
+            ("print float(nil);", "Can't convert nil to a float."),
+            ("print int(1, 2);", "Expected 1 arguments but got 2."),
+        ] {
+            let error = execute(source).unwrap_err();
+            assert_eq!(error.message(), message, "{}", source);
+        }
+        // Floats keep IEEE semantics rather than erroring.
+        assert!(execute("print 1.0 / 0;").is_ok());
+    }
+
+    #[test]
+    fn test_big_integers() {
+        let interpreter = run(r#"
+            var max = 9223372036854775807;
+            var big = max + 1;
+            var back = big - 1;
+            var product = 4611686018427387904 * 4;
+            var literal = 123456789012345678901234567890;
+            var negated = -(-9223372036854775807 - 1);
+            var quotient = literal / 1000000000000000000000;
+            var compared = big > max;
+            var truncated = int(1e19);
+        "#);
+        let get = |name: &str| interpreter.env.borrow().get(name).unwrap().to_string();
+        assert_eq!(get("big"), "9223372036854775808");
+        assert_eq!(
+            interpreter.env.borrow().get("back"),
+            Some(Value::Int(i64::MAX))
+        );
+        assert_eq!(get("product"), "18446744073709551616");
+        assert_eq!(get("literal"), "123456789012345678901234567890");
+        assert_eq!(get("negated"), "9223372036854775808");
+        assert_eq!(get("quotient"), "123456789");
+        assert_eq!(get("compared"), "true");
+        assert_eq!(get("truncated"), "10000000000000000000");
+    }
+
+    #[test]
+    fn test_exact_decimals() {
+        let interpreter = run(r#"
+            var sum = 0.1d + 0.2d;
+            var exact = sum == 0.3d;
+            var third = 1d / 3;
+            var back = third * 3;
+            var total = 19.99d * 3 - 0.97d;
+            var mixed = 0.5d + 1;
+            var floaty = 0.5d + 0.25;
+            var ordered = 0.1d < 0.2d;
+            var remainder = 7.5d % 2;
+            var converted = decimal(0.1) == 0.1d;
+            var parsed = decimal("12.345");
+        "#);
+        let get = |name: &str| interpreter.env.borrow().get(name).unwrap().to_string();
+        assert_eq!(get("sum"), "0.3");
+        assert_eq!(get("exact"), "true");
+        assert_eq!(get("third"), "1/3");
+        assert_eq!(get("back"), "1");
+        assert_eq!(get("total"), "59");
+        assert_eq!(get("mixed"), "1.5");
+        assert_eq!(
+            interpreter.env.borrow().get("floaty"),
+            Some(Value::Number(0.75))
+        );
+        assert_eq!(get("ordered"), "true");
+        assert_eq!(get("remainder"), "1.5");
+        assert_eq!(get("converted"), "true");
+        assert_eq!(get("parsed"), "12.345");
+
+        let error = execute("print 1d / 0;").unwrap_err();
+        assert_eq!(error.message(), "Division by zero.");
+        let error = execute("print decimal(\"abc\");").unwrap_err();
+        assert_eq!(error.message(), "Can't convert \"abc\" to a decimal.");
+    }
+
+    #[test]
+    fn test_power_floor_division_and_bitwise_operators() {
+        let interpreter = run(r#"
+            var negated = -2 ** 2;
+            var tower = 2 ** 3 ** 2;
+            var reciprocal = 2 ** -1;
+            var root = 9 ** 0.5;
+            var half = 0.5d ** 2;
+            var floored = 7 // 2;
+            var negative = -7 // 2;
+            var float_floor = 7.5 // 2;
+            var both = 6 & 3;
+            var either = 6 | 3;
+            var xor = 6 ^ 3;
+            var not = ~5;
+            var big = 1 << 70;
+            var shifted = -8 >> 1;
+            var precedence = 1 + 2 << 1;
+            var even = 4 & 1 == 0;
+        "#);
+        let get = |name: &str| interpreter.env.borrow().get(name).unwrap().to_string();
+        assert_eq!(get("negated"), "-4");
+        assert_eq!(get("tower"), "512");
+        assert_eq!(get("reciprocal"), "0.5");
+        assert_eq!(get("root"), "3.0");
+        assert_eq!(get("half"), "0.25");
+        assert_eq!(get("floored"), "3");
+        assert_eq!(get("negative"), "-4");
+        assert_eq!(
+            interpreter.env.borrow().get("float_floor"),
+            Some(Value::Number(3.0))
+        );
+        assert_eq!(get("both"), "2");
+        assert_eq!(get("either"), "7");
+        assert_eq!(get("xor"), "5");
+        assert_eq!(get("not"), "-6");
+        assert_eq!(get("big"), "1180591620717411303424");
+        assert_eq!(get("shifted"), "-4");
+        assert_eq!(get("precedence"), "6");
+        assert_eq!(get("even"), "true");
+
+        for (source, message) in [
+            ("print 1.5 & 1;", "Bitwise operators need integer operands."),
+            ("print ~\"a\";", "Bitwise operators need integer operands."),
+            ("print 1 << -1;", "Shift amount can't be negative."),
+            ("print 7 // 0;", "Division by zero."),
+            ("print 2 ** 100000000;", "Result of '**' is too large."),
+        ] {
+            let error = execute(source).unwrap_err();
+            assert_eq!(error.message(), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let result = std::thread::Builder::new()
+            .stack_size(Interpreter::STACK_SIZE)
+            .spawn(|| {
+                let interpreter = run(r#"
+                    fun sum(n) {
+                        if (n == 0) return 0;
+                        return n + sum(n - 1);
+                    }
+                    var total = sum(999);
+                "#);
+                let total = interpreter.env.borrow().get("total").unwrap().to_string();
+                let runaway = execute("fun f(n) { return f(n + 1); } f(0);").unwrap_err();
+                // Blocks nest deeper on every call than the call count shows.
+                let blocks = format!(
+                    "fun f(n) {{ if (n == 0) return 0; {} return 1 + f(n - 1); {} }} f(999);",
+                    "{".repeat(30),
+                    "}".repeat(30)
+                );
+                let nested = execute(&blocks).unwrap_err();
+                (
+                    total,
+                    runaway.message().to_string(),
+                    nested.message().to_string(),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            result,
+            (
+                "499500".to_string(),
+                "Stack overflow.".to_string(),
+                "Stack overflow.".to_string()
+            )
+        );
+    }
 }
@@ -1,15 +1,25 @@
+mod class;
+mod diagnostic;
 mod env;
 mod evaluate;
 mod expr;
+mod function;
 mod interpreter;
+mod native;
+mod number;
 mod parser;
 mod parser_error;
+mod resolver;
 mod scanner;
+mod span;
 mod stmt;
 mod token;
 mod token_type;
 
+pub use diagnostic::Diagnostic;
 pub use interpreter::Interpreter;
 pub use parser::Parser;
+pub use parser_error::Error;
+pub use resolver::Resolver;
 pub use scanner::Scanner;
 pub use stmt::Stmt;
@@ -5,7 +5,7 @@ use std::{
 };
 
 use clap::Parser;
-use vakya_interpreter::{Interpreter, Scanner};
+use vakya_interpreter::{Diagnostic, Interpreter, Resolver, Scanner, Stmt};
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser)]
@@ -13,8 +13,44 @@ struct Cli {
     path: Option<std::path::PathBuf>,
 }
 
+fn report(file_name: &str, source: &str, errors: &[vakya_interpreter::Error]) {
+    for error in errors {
+        eprint!("{}", Diagnostic::from(error).render(file_name, source));
+    }
+}
+
+/// Runs `source` as one program. With `echo` set, as in the REPL, the value
+/// of each expression statement is printed.
+fn run(file_name: &str, source: &str, interpreter: &mut Interpreter, echo: bool) {
+    let mut scanner = Scanner::new(source);
+    if let Err(errors) = scanner.scan_tokens() {
+        return report(file_name, source, &errors);
+    }
+    let parser = vakya_interpreter::Parser::new(&scanner.tokens);
+    let statements = match parser.parse() {
+        Ok(statements) => statements,
+        Err(errors) => return report(file_name, source, &errors),
+    };
+    if let Err(errors) = Resolver::new().resolve(&statements) {
+        return report(file_name, source, &errors);
+    }
+    let statements = if echo {
+        statements
+            .into_iter()
+            .map(|stmt| match stmt {
+                Stmt::ExprStmt(expr, span) => Stmt::PrintStmt(expr, span),
+                stmt => stmt,
+            })
+            .collect()
+    } else {
+        statements
+    };
+    let errors = interpreter.interpret(statements);
+    report(file_name, source, &errors);
+}
+
 fn run_file(path: std::path::PathBuf) -> Result<(), std::io::Error> {
-    let file = File::open(path)?;
+    let file = File::open(&path)?;
     let reader = BufReader::new(file);
     let mut source = String::new();
     for line in reader.lines() {
@@ -27,17 +63,13 @@ fn run_file(path: std::path::PathBuf) -> Result<(), std::io::Error> {
         }
     }
 
-    let mut scanner = Scanner::new(&source);
-    scanner.scan_tokens();
-    let parser = vakya_interpreter::Parser::new(&scanner.tokens);
-    let res = parser.parse();
     let mut interpreter = Interpreter::new();
-
-    match res {
-        Ok(statements) => interpreter.interpret(statements),
-        Err(error) => println!("error: {:?}", error),
-    }
-
+    run(
+        &path.display().to_string(),
+        &source,
+        &mut interpreter,
+        false,
+    );
     Ok(())
 }
 
@@ -47,23 +79,19 @@ fn run_prompt() -> Result<(), std::io::Error> {
         println!("> ");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input)?;
-        let mut scanner = Scanner::new(&input);
-        scanner.scan_tokens();
-        let parser = vakya_interpreter::Parser::new(&scanner.tokens);
-        let res = parser.parse();
-
-        match res {
-            Ok(statements) => interpreter.interpret(statements),
-            Err(error) => println!("error: {:?}", error),
-        }
+        run("<stdin>", &input, &mut interpreter, true);
     }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Cli::parse();
-    match args.path {
-        Some(path) => run_file(path)?,
-        None => run_prompt()?,
-    }
+    // Deep recursion in Vakya needs more native stack than the main thread has.
+    let interpreter = std::thread::Builder::new()
+        .stack_size(Interpreter::STACK_SIZE)
+        .spawn(move || match args.path {
+            Some(path) => run_file(path),
+            None => run_prompt(),
+        })?;
+    interpreter.join().expect("interpreter thread panicked")?;
     Ok(())
 }
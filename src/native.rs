@@ -0,0 +1,104 @@
+use crate::env::Env;
+use crate::expr::Value;
+use crate::number::{integer, parse_decimal, to_float, to_rational, truncate_float};
+use crate::parser_error::Error;
+use crate::span::Span;
+use std::rc::Rc;
+
+/// A function built into the interpreter rather than written in Vakya.
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    /// Called with arguments already checked against `arity`, and the span
+    /// of the call for reporting errors.
+    pub function: fn(&[Value], Span) -> Result<Value, Error>,
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// Defines every native function in `globals`.
+pub fn define_natives(globals: &mut Env) {
+    let natives = [
+        NativeFunction {
+            name: "int",
+            arity: 1,
+            function: int,
+        },
+        NativeFunction {
+            name: "float",
+            arity: 1,
+            function: float,
+        },
+        NativeFunction {
+            name: "decimal",
+            arity: 1,
+            function: decimal,
+        },
+    ];
+    for native in natives {
+        globals.define(native.name, Value::Native(Rc::new(native)));
+    }
+}
+
+/// Converts to an integer, truncating floats toward zero.
+fn int(arguments: &[Value], span: Span) -> Result<Value, Error> {
+    let error = || {
+        Error::EvalError(
+            format!("Can't convert {} to an integer.", describe(&arguments[0])),
+            span,
+            None,
+        )
+    };
+    let converted = match &arguments[0] {
+        Value::Int(_) | Value::BigInt(_) => Some(arguments[0].clone()),
+        Value::Rational(rational) => Some(integer(rational.trunc().to_integer())),
+        Value::Number(num) => truncate_float(*num),
+        Value::String(string) => string.trim().parse().ok().map(integer),
+        _ => None,
+    };
+    converted.ok_or_else(error)
+}
+
+fn float(arguments: &[Value], span: Span) -> Result<Value, Error> {
+    let converted = match &arguments[0] {
+        Value::String(string) => string.trim().parse().ok(),
+        value => to_float(value),
+    };
+    converted.map(Value::Number).ok_or_else(|| {
+        Error::EvalError(
+            format!("Can't convert {} to a float.", describe(&arguments[0])),
+            span,
+            None,
+        )
+    })
+}
+
+/// Converts to an exact decimal. Floats convert from their printed form, so
+/// `decimal(0.1)` is exactly one tenth.
+fn decimal(arguments: &[Value], span: Span) -> Result<Value, Error> {
+    let converted = match &arguments[0] {
+        Value::Number(num) if num.is_finite() => parse_decimal(&num.to_string()),
+        Value::String(string) => parse_decimal(string.trim()),
+        value => to_rational(value),
+    };
+    converted.map(Value::Rational).ok_or_else(|| {
+        Error::EvalError(
+            format!("Can't convert {} to a decimal.", describe(&arguments[0])),
+            span,
+            None,
+        )
+    })
+}
+
+/// Names a value in an error message, quoting strings so that empty or
+/// padded ones are still visible.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(string) => format!("\"{}\"", string),
+        value => value.to_string(),
+    }
+}
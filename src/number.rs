@@ -0,0 +1,340 @@
+use crate::expr::Value;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Pow, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+
+/// Decimal exponents beyond this are rejected rather than expanded into
+/// enormous exact values.
+const MAX_DECIMAL_EXPONENT: u64 = 4096;
+
+/// The largest result, in bits, that `**` and `<<` will build.
+const MAX_RESULT_BITS: u64 = 1 << 20;
+
+/// Makes an integer value, using the compact `Int` whenever it fits.
+pub fn integer(value: BigInt) -> Value {
+    match value.to_i64() {
+        Some(int) => Value::Int(int),
+        None => Value::BigInt(value),
+    }
+}
+
+/// Reads a float as the integer it holds after truncating toward zero.
+pub fn truncate_float(num: f64) -> Option<Value> {
+    BigInt::from_f64(num.trunc()).map(integer)
+}
+
+/// Parses a decimal such as `12`, `-0.25` or `1.5e-3` into an exact rational.
+pub fn parse_decimal(text: &str) -> Option<BigRational> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(e) => (&text[..e], text[e + 1..].parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+        return None;
+    }
+
+    let digits: BigInt = format!("{}{}", integer, fraction).parse().ok()?;
+    let digits = if negative { -digits } else { digits };
+    let scale = exponent.checked_sub(fraction.len() as i64)?;
+    if scale.unsigned_abs() > MAX_DECIMAL_EXPONENT {
+        return None;
+    }
+    let power = num_traits::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+    Some(if scale >= 0 {
+        BigRational::from_integer(digits * power)
+    } else {
+        BigRational::new(digits, power)
+    })
+}
+
+/// Writes a rational as an exact decimal when it has one, like `0.125`, and
+/// as a fraction like `1/3` when its decimal expansion never ends.
+pub fn format_rational(value: &BigRational) -> String {
+    if value.is_integer() {
+        return value.numer().to_string();
+    }
+    // Only denominators made of 2s and 5s give a terminating decimal.
+    let (mut rest, mut twos, mut fives) = (value.denom().clone(), 0, 0);
+    let (two, five) = (BigInt::from(2), BigInt::from(5));
+    while rest.is_multiple_of(&two) {
+        rest /= &two;
+        twos += 1;
+    }
+    while rest.is_multiple_of(&five) {
+        rest /= &five;
+        fives += 1;
+    }
+    if !rest.is_one() {
+        return format!("{}/{}", value.numer(), value.denom());
+    }
+
+    let places = twos.max(fives);
+    let scaled = value * BigRational::from_integer(num_traits::pow(BigInt::from(10), places));
+    let digits = scaled.to_integer().abs().to_string();
+    let digits = format!("{:0>width$}", digits, width = places + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - places);
+    let sign = if value.is_negative() { "-" } else { "" };
+    format!("{}{}.{}", sign, integer, fraction)
+}
+
+pub fn to_bigint(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Int(int) => Some(BigInt::from(*int)),
+        Value::BigInt(int) => Some(int.clone()),
+        _ => None,
+    }
+}
+
+pub fn to_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::Rational(rational) => Some(rational.clone()),
+        _ => to_bigint(value).map(BigRational::from_integer),
+    }
+}
+
+pub fn to_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(int) => Some(*int as f64),
+        Value::BigInt(int) => int.to_f64(),
+        Value::Rational(rational) => rational.to_f64(),
+        Value::Number(num) => Some(*num),
+        _ => None,
+    }
+}
+
+/// Negates a number, or returns `None` if the value isn't one.
+pub fn negate(value: &Value) -> Option<Value> {
+    match value {
+        Value::Int(int) => Some(
+            int.checked_neg()
+                .map(Value::Int)
+                .unwrap_or_else(|| integer(-BigInt::from(*int))),
+        ),
+        Value::BigInt(int) => Some(integer(-int)),
+        Value::Rational(rational) => Some(Value::Rational(-rational)),
+        Value::Number(num) => Some(Value::Number(-num)),
+        _ => None,
+    }
+}
+
+/// The operands of an arithmetic or comparison operator, promoted up the
+/// numeric tower to the first type both fit: integers, then exact rationals,
+/// then floats. Integers that overflow `i64` continue as big integers.
+pub enum Operands {
+    Ints(i64, i64),
+    BigInts(BigInt, BigInt),
+    Rationals(BigRational, BigRational),
+    Floats(f64, f64),
+}
+
+impl Operands {
+    /// Returns `None` if either value isn't a number.
+    pub fn of(left: &Value, right: &Value) -> Option<Self> {
+        let rank = |value: &Value| match value {
+            Value::Int(_) | Value::BigInt(_) => Some(0),
+            Value::Rational(_) => Some(1),
+            Value::Number(_) => Some(2),
+            _ => None,
+        };
+        Some(match (rank(left)?.max(rank(right)?), left, right) {
+            (0, Value::Int(left), Value::Int(right)) => Operands::Ints(*left, *right),
+            (0, ..) => Operands::BigInts(to_bigint(left)?, to_bigint(right)?),
+            (1, ..) => Operands::Rationals(to_rational(left)?, to_rational(right)?),
+            _ => Operands::Floats(to_float(left)?, to_float(right)?),
+        })
+    }
+
+    fn apply(
+        self,
+        ints: fn(i64, i64) -> Option<i64>,
+        big_ints: fn(BigInt, BigInt) -> BigInt,
+        rationals: fn(BigRational, BigRational) -> BigRational,
+        floats: fn(f64, f64) -> f64,
+    ) -> Value {
+        match self {
+            Operands::Ints(left, right) => match ints(left, right) {
+                Some(int) => Value::Int(int),
+                None => integer(big_ints(left.into(), right.into())),
+            },
+            Operands::BigInts(left, right) => integer(big_ints(left, right)),
+            Operands::Rationals(left, right) => Value::Rational(rationals(left, right)),
+            Operands::Floats(left, right) => Value::Number(floats(left, right)),
+        }
+    }
+
+    pub fn add(self) -> Value {
+        self.apply(i64::checked_add, |a, b| a + b, |a, b| a + b, |a, b| a + b)
+    }
+
+    pub fn sub(self) -> Value {
+        self.apply(i64::checked_sub, |a, b| a - b, |a, b| a - b, |a, b| a - b)
+    }
+
+    pub fn mul(self) -> Value {
+        self.apply(i64::checked_mul, |a, b| a * b, |a, b| a * b, |a, b| a * b)
+    }
+
+    /// Whether dividing would be an error. Float division by zero is allowed
+    /// and gives an infinity or NaN.
+    pub fn divides_by_zero(&self) -> bool {
+        match self {
+            Operands::Ints(_, right) => *right == 0,
+            Operands::BigInts(_, right) => right.is_zero(),
+            Operands::Rationals(_, right) => right.is_zero(),
+            Operands::Floats(..) => false,
+        }
+    }
+
+    /// Divides, truncating toward zero for integers. Check `divides_by_zero`
+    /// first.
+    pub fn div(self) -> Value {
+        self.apply(i64::checked_div, |a, b| a / b, |a, b| a / b, |a, b| a / b)
+    }
+
+    /// The remainder after `div`, taking the sign of the left operand.
+    pub fn rem(self) -> Value {
+        self.apply(i64::checked_rem, |a, b| a % b, |a, b| a % b, |a, b| a % b)
+    }
+
+    /// Divides and rounds down, always giving an integer unless either side
+    /// is a float. Check `divides_by_zero` first.
+    pub fn floor_div(self) -> Value {
+        match self {
+            // The one quotient that overflows `i64`.
+            Operands::Ints(i64::MIN, -1) => integer(-BigInt::from(i64::MIN)),
+            Operands::Ints(left, right) => Value::Int(Integer::div_floor(&left, &right)),
+            Operands::BigInts(left, right) => integer(left.div_floor(&right)),
+            Operands::Rationals(left, right) => integer((left / right).floor().to_integer()),
+            Operands::Floats(left, right) => Value::Number((left / right).floor()),
+        }
+    }
+
+    /// Raises the left operand to the power of the right. Integer and exact
+    /// decimal bases stay exact for integer exponents, except that a negative
+    /// integer exponent on an integer gives a float.
+    pub fn pow(self) -> Result<Value, String> {
+        let too_large = || "Result of '**' is too large.".to_string();
+        match self {
+            Operands::Ints(base, exponent) => Operands::BigInts(base.into(), exponent.into()).pow(),
+            Operands::BigInts(base, exponent) => {
+                if exponent.is_negative() {
+                    let base = base.to_f64().unwrap_or(f64::NAN);
+                    let exponent = exponent.to_f64().unwrap_or(f64::NEG_INFINITY);
+                    return Ok(Value::Number(base.powf(exponent)));
+                }
+                // 0, 1 and -1 stay small whatever the exponent.
+                if base.magnitude().is_zero() || base.magnitude().is_one() {
+                    let odd = exponent.is_odd();
+                    return Ok(match base.to_i64() {
+                        Some(0) if exponent.is_zero() => Value::Int(1),
+                        Some(-1) if !odd => Value::Int(1),
+                        Some(base) => Value::Int(base),
+                        None => unreachable!(),
+                    });
+                }
+                let exponent = exponent
+                    .to_u64()
+                    .filter(|exponent| exponent.saturating_mul(base.bits()) <= MAX_RESULT_BITS)
+                    .ok_or_else(too_large)?;
+                Ok(integer(Pow::pow(base, exponent)))
+            }
+            Operands::Rationals(base, exponent) if exponent.is_integer() => {
+                let exponent = exponent.to_integer();
+                if base.is_zero() && exponent.is_negative() {
+                    return Err("Division by zero.".to_string());
+                }
+                let bits = base.numer().bits().max(base.denom().bits());
+                let exponent = exponent
+                    .to_i32()
+                    .filter(|exponent| (exponent.unsigned_abs() as u64) * bits <= MAX_RESULT_BITS)
+                    .ok_or_else(too_large)?;
+                Ok(Value::Rational(Pow::pow(base, exponent)))
+            }
+            Operands::Rationals(base, exponent) => Ok(Value::Number(
+                base.to_f64()
+                    .unwrap_or(f64::NAN)
+                    .powf(exponent.to_f64().unwrap_or(f64::NAN)),
+            )),
+            Operands::Floats(base, exponent) => Ok(Value::Number(base.powf(exponent))),
+        }
+    }
+
+    /// Orders the operands; `None` when either is NaN.
+    pub fn compare(&self) -> Option<Ordering> {
+        match self {
+            Operands::Ints(left, right) => left.partial_cmp(right),
+            Operands::BigInts(left, right) => left.partial_cmp(right),
+            Operands::Rationals(left, right) => left.partial_cmp(right),
+            Operands::Floats(left, right) => left.partial_cmp(right),
+        }
+    }
+}
+
+/// Shifts an integer left, or right for `>>`, by a non-negative amount.
+/// Right shifts round toward negative infinity.
+pub fn shift(value: BigInt, amount: &BigInt, left: bool) -> Result<Value, String> {
+    if amount.is_negative() {
+        return Err("Shift amount can't be negative.".to_string());
+    }
+    if left {
+        let amount = amount
+            .to_u64()
+            .filter(|amount| amount.saturating_add(value.bits()) <= MAX_RESULT_BITS)
+            .ok_or_else(|| "Result of '<<' is too large.".to_string())?;
+        return Ok(integer(value << amount));
+    }
+    // Shifting out every bit leaves 0, or -1 for negative numbers.
+    let amount = amount.to_u64().unwrap_or(u64::MAX).min(value.bits() + 1);
+    Ok(integer(value >> amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rational(text: &str) -> BigRational {
+        parse_decimal(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(rational("0.1"), BigRational::new(1.into(), 10.into()));
+        assert_eq!(rational("-2.50"), BigRational::new((-5).into(), 2.into()));
+        assert_eq!(rational("1.5e3"), BigRational::from_integer(1500.into()));
+        assert_eq!(rational("25e-2"), BigRational::new(1.into(), 4.into()));
+        assert_eq!(parse_decimal(".5"), None);
+        assert_eq!(parse_decimal("1.2.3"), None);
+        assert_eq!(parse_decimal("1e99999"), None);
+    }
+
+    #[test]
+    fn test_format_rational() {
+        assert_eq!(format_rational(&rational("0.1")), "0.1");
+        assert_eq!(format_rational(&rational("-0.125")), "-0.125");
+        assert_eq!(format_rational(&rational("12.5")), "12.5");
+        assert_eq!(format_rational(&rational("3.000")), "3");
+        assert_eq!(
+            format_rational(&BigRational::new((-1).into(), 3.into())),
+            "-1/3"
+        );
+    }
+
+    #[test]
+    fn test_int_overflow_promotes() {
+        let sum = Operands::of(&Value::Int(i64::MAX), &Value::Int(1))
+            .unwrap()
+            .add();
+        assert_eq!(sum, Value::BigInt(BigInt::from(i64::MAX) + 1));
+        // Results that fit again drop back to `Int`.
+        let back = Operands::of(&sum, &Value::Int(-1)).unwrap().add();
+        assert_eq!(back, Value::Int(i64::MAX));
+    }
+}
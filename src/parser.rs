@@ -1,13 +1,78 @@
-use crate::expr::{Expr, Value};
+use crate::expr::{Depth, Expr, Value};
+use crate::number::{integer, parse_decimal};
 use crate::parser_error::Error;
-use crate::stmt::Stmt;
+use crate::span::Span;
+use crate::stmt::{FunctionDecl, Stmt};
 use crate::token::Token;
 use crate::token_type::TokenType;
+use num_bigint::BigInt;
 use std::cell::Cell;
+use std::rc::Rc;
+
+/// Binary operators from loosest to tightest binding, below `and` and above
+/// unary operators. Every level groups to the left; `**` binds tighter still
+/// and is handled by `power`.
+const BINARY_PRECEDENCE: &[&[TokenType]] = &[
+    &[TokenType::BangEqual, TokenType::EqualEqual],
+    &[
+        TokenType::Greater,
+        TokenType::GreaterEqual,
+        TokenType::Less,
+        TokenType::LessEqual,
+    ],
+    &[TokenType::Pipe],
+    &[TokenType::Caret],
+    &[TokenType::Ampersand],
+    &[TokenType::LessLess, TokenType::GreaterGreater],
+    &[TokenType::Minus, TokenType::Plus],
+    &[
+        TokenType::Slash,
+        TokenType::SlashSlash,
+        TokenType::Star,
+        TokenType::Percent,
+    ],
+];
+
+/// How deeply statements and expressions may nest. Parsing, resolving and
+/// running all recurse over the syntax tree, so a limit here keeps them from
+/// exhausting the native stack.
+const MAX_NESTING: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+impl FunctionKind {
+    fn name(self) -> &'static str {
+        match self {
+            FunctionKind::Method | FunctionKind::Initializer => "method",
+            _ => "function",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
 
 pub struct Parser<'a> {
     tokens: &'a [Token],
     pub current: Cell<usize>,
+    // How many loop bodies enclose the statement being parsed.
+    loop_depth: Cell<usize>,
+    // The innermost function body enclosing the statement being parsed.
+    function_kind: Cell<FunctionKind>,
+    // The innermost class body enclosing the statement being parsed.
+    class_kind: Cell<ClassKind>,
+    // How deeply the syntax tree being built is nested so far.
+    depth: Cell<usize>,
 }
 
 impl<'a> Parser<'a> {
@@ -15,28 +80,141 @@ impl<'a> Parser<'a> {
         Parser {
             tokens,
             current: Cell::new(0),
+            loop_depth: Cell::new(0),
+            function_kind: Cell::new(FunctionKind::None),
+            class_kind: Cell::new(ClassKind::None),
+            depth: Cell::new(0),
         }
     }
 
-    pub fn parse(&self) -> Result<Vec<Stmt>, Error> {
+    /// Parses the whole token stream, recovering after each syntax error so
+    /// that every error in the source is reported at once.
+    pub fn parse(&self) -> Result<Vec<Stmt>, Vec<Error>> {
         let mut statments = Vec::new();
+        let mut errors = Vec::new();
         // the last token is ";" so do not consume it.
         while self.current.get() < self.tokens.len() - 1 {
-            let stmt = self.declaration()?;
-            statments.push(stmt);
+            let start = self.current.get();
+            self.depth.set(0);
+            match self.declaration() {
+                Ok(stmt) => statments.push(stmt),
+                Err(error) => {
+                    errors.push(error);
+                    self.synchronize(start);
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(statments)
+        } else {
+            Err(errors)
         }
-        Ok(statments)
     }
 
     fn declaration(&self) -> Result<Stmt, Error> {
+        if self.match_next(TokenType::Class) {
+            return self.class_declaration();
+        }
+        if self.match_next(TokenType::Fun) {
+            return Ok(Stmt::Function(self.function(FunctionKind::Function)?));
+        }
         if self.match_next(TokenType::Var) {
             return self.var_declaration();
         }
         self.statement()
     }
 
+    fn class_declaration(&self) -> Result<Stmt, Error> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect class name.")?
+            .clone();
+        let superclass = if self.match_next(TokenType::Less) {
+            let superclass = self
+                .consume(TokenType::Identifier, "Expect superclass name.")?
+                .clone();
+            if superclass.lexeme == name.lexeme {
+                return Err(Error::ParserError(
+                    "A class can't inherit from itself.".to_string(),
+                    superclass.span,
+                    None,
+                ));
+            }
+            Some(Box::new(Expr::Variable(superclass, Depth::default())))
+        } else {
+            None
+        };
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        let kind = if superclass.is_some() {
+            ClassKind::Subclass
+        } else {
+            ClassKind::Class
+        };
+        let enclosing_kind = self.class_kind.replace(kind);
+        let methods = self.class_body();
+        self.class_kind.set(enclosing_kind);
+        Ok(Stmt::Class(name, superclass, methods?))
+    }
+
+    fn class_body(&self) -> Result<Vec<Rc<FunctionDecl>>, Error> {
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function(FunctionKind::Method)?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(methods)
+    }
+
+    fn function(&self, kind: FunctionKind) -> Result<Rc<FunctionDecl>, Error> {
+        let name = self
+            .consume(
+                TokenType::Identifier,
+                &format!("Expect {} name.", kind.name()),
+            )?
+            .clone();
+        let kind = if kind == FunctionKind::Method && name.lexeme == "init" {
+            FunctionKind::Initializer
+        } else {
+            kind
+        };
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind.name()),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name.")?
+                        .clone(),
+                );
+                if !self.match_next(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind.name()),
+        )?;
+        // A function body starts outside of any loop, so `break` can't escape it.
+        let loop_depth = self.loop_depth.replace(0);
+        let enclosing_kind = self.function_kind.replace(kind);
+        let body = self.block();
+        self.function_kind.set(enclosing_kind);
+        self.loop_depth.set(loop_depth);
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body: body?,
+        }))
+    }
+
     fn var_declaration(&self) -> Result<Stmt, Error> {
-        let name = self.consume(TokenType::Identifier, "Expect variable name.");
+        let keyword = self.previous().span;
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
         let initializer = if self.match_next(TokenType::Equal) {
             Some(self.expression()?)
         } else {
@@ -45,48 +223,274 @@ impl<'a> Parser<'a> {
         self.consume(
             TokenType::SemiColon,
             "Expect ';' after variable declaration.",
-        );
-        Ok(Stmt::VarStmt(name, initializer))
+        )?;
+        let span = keyword.to(self.previous().span);
+        Ok(Stmt::VarStmt(name, initializer, span))
     }
 
     fn statement(&self) -> Result<Stmt, Error> {
         if self.match_next(TokenType::Print) {
             return self.print_statement();
         }
+        if self.match_next(TokenType::LeftBrace) {
+            let open = self.previous().span;
+            let statements = self.block()?;
+            return Ok(Stmt::Block(statements, open.to(self.previous().span)));
+        }
+        if self.match_next(TokenType::If) {
+            return self.if_statement();
+        }
+        if self.match_next(TokenType::While) {
+            return self.while_statement();
+        }
+        if self.match_next(TokenType::For) {
+            return self.for_statement();
+        }
+        if self.match_next(TokenType::Return) {
+            return self.return_statement();
+        }
+        if self.match_next(TokenType::Break) {
+            return self.loop_jump_statement(Stmt::Break);
+        }
+        if self.match_next(TokenType::Continue) {
+            return self.loop_jump_statement(Stmt::Continue);
+        }
         self.expression_statement()
     }
 
+    fn return_statement(&self) -> Result<Stmt, Error> {
+        let keyword = self.previous().clone();
+        if self.function_kind.get() == FunctionKind::None {
+            return Err(Error::ParserError(
+                "Can't return from top-level code.".to_string(),
+                keyword.span,
+                None,
+            ));
+        }
+        let value = if self.check(TokenType::SemiColon) {
+            None
+        } else if self.function_kind.get() == FunctionKind::Initializer {
+            return Err(Error::ParserError(
+                "Can't return a value from an initializer.".to_string(),
+                self.peek().span,
+                None,
+            ));
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::SemiColon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    fn loop_jump_statement(&self, stmt: fn(Token) -> Stmt) -> Result<Stmt, Error> {
+        let keyword = self.previous().clone();
+        if self.loop_depth.get() == 0 {
+            return Err(Error::ParserError(
+                format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+                keyword.span,
+                None,
+            ));
+        }
+        self.consume(
+            TokenType::SemiColon,
+            &format!("Expect ';' after '{}'.", keyword.lexeme),
+        )?;
+        Ok(stmt(keyword))
+    }
+
+    fn loop_body(&self) -> Result<Box<Stmt>, Error> {
+        self.loop_depth.set(self.loop_depth.get() + 1);
+        let body = self.nested(|| self.statement());
+        self.loop_depth.set(self.loop_depth.get() - 1);
+        Ok(Box::new(body?))
+    }
+
+    fn while_statement(&self) -> Result<Stmt, Error> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.loop_body()?;
+        let span = keyword.to(body.span());
+        Ok(Stmt::While(condition, body, span))
+    }
+
+    fn for_statement(&self) -> Result<Stmt, Error> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        let initializer = if self.match_next(TokenType::SemiColon) {
+            None
+        } else if self.match_next(TokenType::Var) {
+            Some(Box::new(self.var_declaration()?))
+        } else {
+            Some(Box::new(self.expression_statement()?))
+        };
+        let condition = if self.check(TokenType::SemiColon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::SemiColon, "Expect ';' after loop condition.")?;
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = self.loop_body()?;
+        let span = keyword.to(body.span());
+        Ok(Stmt::For(initializer, condition, increment, body, span))
+    }
+
+    fn if_statement(&self) -> Result<Stmt, Error> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+        let then_branch = Box::new(self.nested(|| self.statement())?);
+        // Eagerly claiming the `else` binds it to the nearest `if`.
+        let else_branch = if self.match_next(TokenType::Else) {
+            Some(Box::new(self.nested(|| self.statement())?))
+        } else {
+            None
+        };
+        let span = keyword.to(self.previous().span);
+        Ok(Stmt::If(condition, then_branch, else_branch, span))
+    }
+
+    fn block(&self) -> Result<Vec<Stmt>, Error> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.nested(|| self.declaration())?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
     fn expression_statement(&self) -> Result<Stmt, Error> {
         let expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after value.");
-        Ok(Stmt::ExprStmt(expr))
+        let semicolon = self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
+        let span = expr.span().to(semicolon.span);
+        Ok(Stmt::ExprStmt(expr, span))
     }
 
     fn print_statement(&self) -> Result<Stmt, Error> {
+        let keyword = self.previous().span;
         let expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after value.");
-        Ok(Stmt::PrintStmt(expr))
+        let semicolon = self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
+        Ok(Stmt::PrintStmt(expr, keyword.to(semicolon.span)))
     }
 
     fn expression(&self) -> Result<Box<Expr>, Error> {
-        self.equality()
+        self.nested(|| self.assignment())
     }
 
-    fn equality(&self) -> Result<Box<Expr>, Error> {
-        let mut expr = self.comparison()?;
-        while self.match_next(TokenType::BangEqual) || self.match_next(TokenType::EqualEqual) {
-            let operator = self.previous();
-            let right = self.comparison()?;
+    fn assignment(&self) -> Result<Box<Expr>, Error> {
+        let expr = self.or()?;
+        if self.match_next(TokenType::Equal) {
+            // Assignment is right-associative, so parse the value recursively.
+            let value = self.nested(|| self.assignment())?;
+            let target_span = expr.span();
+            return match *expr {
+                Expr::Variable(name, _) => {
+                    Ok(Box::new(Expr::Assign(name, value, Depth::default())))
+                }
+                Expr::Get(object, name) => Ok(Box::new(Expr::Set(object, name, value))),
+                _ => Err(Error::ParserError(
+                    "Invalid assignment target.".to_string(),
+                    target_span,
+                    None,
+                )),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn or(&self) -> Result<Box<Expr>, Error> {
+        let depth = self.depth.get();
+        let mut expr = self.and()?;
+        while self.match_next(TokenType::Or) {
+            self.deepen()?;
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Box::new(Expr::Logical(expr, operator, right));
+        }
+        self.depth.set(depth);
+        Ok(expr)
+    }
+
+    fn and(&self) -> Result<Box<Expr>, Error> {
+        let depth = self.depth.get();
+        let mut expr = self.binary(0)?;
+        while self.match_next(TokenType::And) {
+            self.deepen()?;
+            let operator = self.previous().clone();
+            let right = self.binary(0)?;
+            expr = Box::new(Expr::Logical(expr, operator, right));
+        }
+        self.depth.set(depth);
+        Ok(expr)
+    }
+
+    /// Parses binary operators from `BINARY_PRECEDENCE[level]` onward, each
+    /// level binding tighter than the one before it.
+    fn binary(&self, level: usize) -> Result<Box<Expr>, Error> {
+        let Some(operators) = BINARY_PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let depth = self.depth.get();
+        let mut expr = self.binary(level + 1)?;
+        while operators
+            .iter()
+            .any(|operator| self.match_next(operator.clone()))
+        {
+            // Each operator nests the expression so far one level deeper.
+            self.deepen()?;
+            let operator = self.previous().clone();
+            let right = self.binary(level + 1)?;
             expr = Box::new(Expr::Binary(expr, operator, right));
         }
+        self.depth.set(depth);
         Ok(expr)
     }
 
-    fn previous(&self) -> &Token {
+    /// The token about to be consumed; the trailing `Eof` once input runs out.
+    fn peek(&self) -> &'a Token {
+        let current = self.current.get().min(self.tokens.len() - 1);
+        &self.tokens[current]
+    }
+
+    /// Where to report an error about the upcoming token. Running out of
+    /// input is reported just past the last token rather than on whatever
+    /// blank line the `Eof` ended up on.
+    fn error_span(&self) -> Span {
+        let current = self.current.get();
+        if !self.is_at_end() || current == 0 {
+            return self.peek().span;
+        }
+        let last = &self.tokens[current - 1];
+        Span {
+            offset: last.span.offset + last.span.len,
+            len: 0,
+            line: last.span.line,
+            column: last.span.column + last.lexeme.chars().count(),
+        }
+    }
+
+    fn previous(&self) -> &'a Token {
         let current = self.current.get();
         &self.tokens[current - 1]
     }
 
+    fn is_at_end(&self) -> bool {
+        let current = self.current.get();
+        current >= self.tokens.len() || self.tokens[current].token_type == TokenType::Eof
+    }
+
+    fn check(&self, expected: TokenType) -> bool {
+        !self.is_at_end() && self.tokens[self.current.get()].token_type == expected
+    }
+
     fn match_next(&self, expected: TokenType) -> bool {
         if self.current.get() >= self.tokens.len() {
             return false;
@@ -102,98 +506,361 @@ impl<'a> Parser<'a> {
         true
     }
 
-    fn comparison(&self) -> Result<Box<Expr>, Error> {
-        let mut expr = self.term()?;
-        while self.match_next(TokenType::Greater)
-            || self.match_next(TokenType::GreaterEqual)
-            || self.match_next(TokenType::Less)
-            || self.match_next(TokenType::LessEqual)
+    fn unary(&self) -> Result<Box<Expr>, Error> {
+        if self.match_next(TokenType::Minus)
+            || self.match_next(TokenType::Bang)
+            || self.match_next(TokenType::Tilde)
         {
-            let operator = self.previous();
-            let right = self.term()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+            let operator = self.previous().clone();
+            let right = self.nested(|| self.unary())?;
+            return Ok(Box::new(Expr::Unary(operator, right)));
         }
-        Ok(expr)
+        self.power()
     }
 
-    fn term(&self) -> Result<Box<Expr>, Error> {
-        let mut expr = self.factor()?;
-        while self.match_next(TokenType::Minus) || self.match_next(TokenType::Plus) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    /// `**` binds tighter than a unary operator on its left, so `-2 ** 2` is
+    /// `-(2 ** 2)`, and groups to the right. Its right operand may itself be
+    /// unary, as in `2 ** -1`.
+    fn power(&self) -> Result<Box<Expr>, Error> {
+        let expr = self.call()?;
+        if self.match_next(TokenType::StarStar) {
+            let operator = self.previous().clone();
+            let right = self.nested(|| self.unary())?;
+            return Ok(Box::new(Expr::Binary(expr, operator, right)));
         }
         Ok(expr)
     }
 
-    fn factor(&self) -> Result<Box<Expr>, Error> {
-        let mut expr: Box<Expr> = self.unary()?;
-        while self.match_next(TokenType::Slash) || self.match_next(TokenType::Star) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    fn call(&self) -> Result<Box<Expr>, Error> {
+        let depth = self.depth.get();
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_next(TokenType::LeftParen) {
+                self.deepen()?;
+                expr = self.finish_call(expr)?;
+            } else if self.match_next(TokenType::Dot) {
+                self.deepen()?;
+                let name = self
+                    .consume(TokenType::Identifier, "Expect property name after '.'.")?
+                    .clone();
+                expr = Box::new(Expr::Get(expr, name));
+            } else {
+                break;
+            }
         }
-
+        self.depth.set(depth);
         Ok(expr)
     }
 
-    fn unary(&self) -> Result<Box<Expr>, Error> {
-        if self.match_next(TokenType::Minus) || self.match_next(TokenType::Bang) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Box::new(Expr::Unary(operator, right)));
+    /// Parses with `parse` one level deeper in the syntax tree.
+    fn nested<T>(&self, parse: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let depth = self.depth.get();
+        self.deepen()?;
+        let result = parse();
+        self.depth.set(depth);
+        result
+    }
+
+    /// Counts one more level of nesting, failing once it passes `MAX_NESTING`.
+    fn deepen(&self) -> Result<(), Error> {
+        if self.depth.get() == MAX_NESTING {
+            return Err(Error::ParserError(
+                "Code is nested too deeply.".to_string(),
+                self.error_span(),
+                None,
+            ));
+        }
+        self.depth.set(self.depth.get() + 1);
+        Ok(())
+    }
+
+    fn finish_call(&self, callee: Box<Expr>) -> Result<Box<Expr>, Error> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                arguments.push(*self.expression()?);
+                if !self.match_next(TokenType::Comma) {
+                    break;
+                }
+            }
         }
-        self.primary()
+        let paren = self
+            .consume(TokenType::RightParen, "Expect ')' after arguments.")?
+            .clone();
+        Ok(Box::new(Expr::Call(callee, paren, arguments)))
+    }
+
+    /// Wraps `value` in a literal spanning the token just consumed.
+    fn literal(&self, value: Value) -> Box<Expr> {
+        Box::new(Expr::Literal(value, self.previous().span))
     }
 
     fn primary(&self) -> Result<Box<Expr>, Error> {
         if self.match_next(TokenType::False) {
-            return Ok(Box::new(Expr::Literal(Value::Boolean(false))));
+            return Ok(self.literal(Value::Boolean(false)));
         }
         if self.match_next(TokenType::True) {
-            return Ok(Box::new(Expr::Literal(Value::Boolean(true))));
+            return Ok(self.literal(Value::Boolean(true)));
         }
         if self.match_next(TokenType::Nil) {
-            return Ok(Box::new(Expr::Literal(Value::Nil)));
+            return Ok(self.literal(Value::Nil));
         }
         if self.match_next(TokenType::String) {
-            return Ok(Box::new(Expr::Literal(Value::String(
-                self.previous().literal.clone(),
-            ))));
+            return Ok(self.literal(Value::String(self.previous().literal.clone())));
+        }
+
+        if self.match_next(TokenType::Interpolation) {
+            return self.interpolation();
         }
 
+        if self.match_next(TokenType::Integer) {
+            let value = self.previous().literal.parse::<BigInt>().unwrap();
+            return Ok(self.literal(integer(value)));
+        }
+        if self.match_next(TokenType::Decimal) {
+            let value = parse_decimal(&self.previous().literal).unwrap();
+            return Ok(self.literal(Value::Rational(value)));
+        }
         if self.match_next(TokenType::Number) {
-            return Ok(Box::new(Expr::Literal(Value::Number(
+            return Ok(self.literal(Value::Number(
                 self.previous().literal.parse::<f64>().unwrap(),
-            ))));
+            )));
+        }
+        if self.match_next(TokenType::Super) {
+            let keyword = self.previous().clone();
+            match self.class_kind.get() {
+                ClassKind::None => {
+                    return Err(Error::ParserError(
+                        "Can't use 'super' outside of a class.".to_string(),
+                        keyword.span,
+                        None,
+                    ))
+                }
+                ClassKind::Class => {
+                    return Err(Error::ParserError(
+                        "Can't use 'super' in a class with no superclass.".to_string(),
+                        keyword.span,
+                        None,
+                    ))
+                }
+                ClassKind::Subclass => {}
+            }
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expect superclass method name.")?
+                .clone();
+            return Ok(Box::new(Expr::Super(keyword, method, Depth::default())));
+        }
+        if self.match_next(TokenType::This) {
+            if self.class_kind.get() == ClassKind::None {
+                return Err(Error::ParserError(
+                    "Can't use 'this' outside of a class.".to_string(),
+                    self.previous().span,
+                    None,
+                ));
+            }
+            return Ok(Box::new(Expr::This(
+                self.previous().clone(),
+                Depth::default(),
+            )));
         }
         if self.match_next(TokenType::Identifier) {
-            return Ok(Box::new(Expr::Variable(self.previous())));
+            return Ok(Box::new(Expr::Variable(
+                self.previous().clone(),
+                Depth::default(),
+            )));
         }
 
         if self.match_next(TokenType::LeftParen) {
+            let open = self.previous();
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.");
-            return Ok(Box::new(Expr::Grouping(expr)));
+            let close = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            return Ok(Box::new(Expr::Grouping(expr, open.span.to(close.span))));
         }
         Err(Error::ParserError(
-            "No matching clause in primary".to_string(),
+            "Expect expression.".to_string(),
+            self.error_span(),
+            None,
         ))
     }
 
-    fn consume(&self, expected: TokenType, message: &str) -> &Token {
+    /// Parses the rest of a string whose first piece has just been matched,
+    /// alternating between literal pieces and `${...}` expressions.
+    fn interpolation(&self) -> Result<Box<Expr>, Error> {
+        let start = self.previous().span;
+        let mut parts = Vec::new();
+        loop {
+            let piece = self.previous();
+            parts.push(*self.literal(Value::String(piece.literal.clone())));
+            if piece.token_type == TokenType::String {
+                break;
+            }
+            parts.push(*self.expression()?);
+            if !self.match_next(TokenType::Interpolation) {
+                self.consume(
+                    TokenType::String,
+                    "Expect '}' after interpolated expression.",
+                )?;
+            }
+        }
+        let span = start.to(self.previous().span);
+        Ok(Box::new(Expr::Interpolation(parts, span)))
+    }
+
+    fn consume(&self, expected: TokenType, message: &str) -> Result<&'a Token, Error> {
+        if !self.check(expected.clone()) {
+            let error = Error::ParserError(message.to_string(), self.error_span(), None);
+            return Err(match expected {
+                TokenType::SemiColon => error.with_help("statements must end with a ';'"),
+                _ => error,
+            });
+        }
         let current = self.current.get();
-        if current >= self.tokens.len() {
-            panic!("error ${message}")
+        self.current.set(current + 1);
+        Ok(&self.tokens[current])
+    }
+
+    /// Discards tokens until the start of the next statement, so that one
+    /// mistake doesn't cascade into a flood of follow-on errors. `start` is
+    /// where the failed top-level statement began; if the error struck inside
+    /// braces opened since then, the rest of them is skipped up to the
+    /// matching `}` so that it isn't misread as top-level code.
+    fn synchronize(&self, start: usize) {
+        let mut open_braces =
+            self.tokens[start..self.current.get()]
+                .iter()
+                .fold(0usize, |open, token| match token.token_type {
+                    TokenType::LeftBrace => open + 1,
+                    TokenType::RightBrace => open.saturating_sub(1),
+                    _ => open,
+                });
+        if open_braces > 0 {
+            while !self.is_at_end() && open_braces > 0 {
+                match self.tokens[self.current.get()].token_type {
+                    TokenType::LeftBrace => open_braces += 1,
+                    TokenType::RightBrace => open_braces -= 1,
+                    _ => {}
+                }
+                self.advance();
+            }
+            return;
         }
-        if self.tokens[current].token_type == TokenType::Eof {
-            panic!("error ${message}")
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::SemiColon {
+                return;
+            }
+            match self.tokens[self.current.get()].token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => self.advance(),
+            }
         }
-        if self.tokens[current].token_type != expected {
-            panic!("error ${message}")
+    }
+
+    fn advance(&self) {
+        if !self.is_at_end() {
+            self.current.set(self.current.get() + 1);
         }
-        let token = &self.tokens[current];
-        self.current.set(current + 1);
-        token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<Error>> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens()?;
+        Parser::new(&scanner.tokens).parse()
+    }
+
+    #[test]
+    fn test_missing_semicolon_is_an_error() {
+        let errors = parse("print 1").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            matches!(&errors[0], Error::ParserError(message, ..) if message == "Expect ';' after value.")
+        );
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let errors = parse("var = 1;\nprint 2\nvar b = 3;\nfun (a) {}\nprint (4;").unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "parser error Expect variable name.",
+                "parser error Expect ';' after value.",
+                "parser error Expect function name.",
+                "parser error Expect ')' after expression.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_error_inside_block() {
+        let errors = parse("{ var a = ; print a; }\nprint 1 +;\nprint 2;").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        let errors =
+            parse("class A {\n  f() { if (x) { print ; } }\n  g( {}\n}\nprint 1 +;\nprint 2;")
+                .unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|error| error.message()).collect();
+        assert_eq!(messages, vec!["Expect expression.", "Expect expression."]);
+    }
+
+    #[test]
+    fn test_statement_spans() {
+        let source =
+            "print 1;\nx = 2;\nif (x) { print x; } else print 3;\nwhile (x) x = nil;\nvar y;";
+        let statements = parse(source).unwrap();
+        let text: Vec<_> = statements
+            .iter()
+            .map(|stmt| {
+                let span = stmt.span();
+                &source[span.offset..span.offset + span.len]
+            })
+            .collect();
+        assert_eq!(
+            text,
+            vec![
+                "print 1;",
+                "x = 2;",
+                "if (x) { print x; } else print 3;",
+                "while (x) x = nil;",
+                "var y;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nesting_too_deep() {
+        let messages = std::thread::Builder::new()
+            .stack_size(crate::Interpreter::STACK_SIZE)
+            .spawn(|| {
+                let sources = [
+                    format!("print {}1{};", "(".repeat(300), ")".repeat(300)),
+                    format!("print {}1;", "-".repeat(300)),
+                    format!("print 1{};", " + 1".repeat(300)),
+                    format!("{}{}", "{".repeat(300), "}".repeat(300)),
+                ];
+                sources
+                    .iter()
+                    .map(|source| parse(source).unwrap_err()[0].message().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(messages, vec!["Code is nested too deeply."; 4]);
+        assert!(parse(&format!("print {}1{};", "(".repeat(50), ")".repeat(50))).is_ok());
     }
 }
@@ -1,9 +1,59 @@
+use crate::span::Span;
 use thiserror::Error;
 
+/// Every error carries a message, the span it points at and an optional hint
+/// on how to fix it, set with [`Error::with_help`].
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
+    /// A lexical error, along with the source text that could not be scanned.
+    #[error("scanner error {0}")]
+    ScannerError(String, Span, String, Option<&'static str>),
     #[error("parser error {0}")]
-    ParserError(String),
+    ParserError(String, Span, Option<&'static str>),
+    #[error("resolver error {0}")]
+    ResolverError(String, Span, Option<&'static str>),
     #[error("eval error error {0}")]
-    EvalError(String),
+    EvalError(String, Span, Option<&'static str>),
+}
+
+impl Error {
+    /// Attaches a hint on how to fix the error, shown with its diagnostic.
+    pub fn with_help(mut self, hint: &'static str) -> Error {
+        match &mut self {
+            Error::ScannerError(_, _, _, help)
+            | Error::ParserError(_, _, help)
+            | Error::ResolverError(_, _, help)
+            | Error::EvalError(_, _, help) => *help = Some(hint),
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::ScannerError(message, ..)
+            | Error::ParserError(message, ..)
+            | Error::ResolverError(message, ..)
+            | Error::EvalError(message, ..) => message,
+        }
+    }
+
+    /// The source the error points at.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::ScannerError(_, span, ..)
+            | Error::ParserError(_, span, _)
+            | Error::ResolverError(_, span, _)
+            | Error::EvalError(_, span, _) => *span,
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            Error::ScannerError(_, _, _, help)
+            | Error::ParserError(_, _, help)
+            | Error::ResolverError(_, _, help)
+            | Error::EvalError(_, _, help) => *help,
+        }
+    }
 }
@@ -0,0 +1,301 @@
+use crate::expr::{Depth, Expr};
+use crate::parser_error::Error;
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::token::Token;
+use std::collections::HashMap;
+
+/// Walks the parsed statements once before they are run, recording for every
+/// variable reference how many scopes out its declaration lives.
+///
+/// Scopes here must mirror the environments the interpreter creates at
+/// runtime. Globals are not tracked and are looked up by name instead.
+#[derive(Default)]
+pub struct Resolver {
+    // Each scope maps a name to whether its initializer has finished.
+    scopes: Vec<HashMap<String, bool>>,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves every statement, carrying on past mistakes so that all of
+    /// them are reported at once.
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Vec<Error>> {
+        self.resolve_stmts(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::ExprStmt(expr, _) | Stmt::PrintStmt(expr, _) => self.resolve_expr(expr),
+            Stmt::VarStmt(name, initializer, _) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block(statements, _) => {
+                self.in_scope(|resolver| resolver.resolve_stmts(statements))
+            }
+            Stmt::If(condition, then_branch, else_branch, _) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While(condition, body, _) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+            Stmt::For(initializer, condition, increment, body, _) => self.in_scope(|resolver| {
+                if let Some(initializer) = initializer {
+                    resolver.resolve_stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    resolver.resolve_expr(condition);
+                }
+                if let Some(increment) = increment {
+                    resolver.resolve_expr(increment);
+                }
+                resolver.resolve_stmt(body);
+            }),
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+            Stmt::Function(declaration) => {
+                // Declared and defined up front so the function can recurse.
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration);
+            }
+            Stmt::Return(_, value) => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Class(name, superclass, methods) => {
+                self.declare(name);
+                self.define(name);
+                if let Some(superclass) = superclass {
+                    self.resolve_expr(superclass);
+                    self.begin_scope();
+                    self.define_name("super");
+                }
+                self.begin_scope();
+                self.define_name("this");
+                for method in methods {
+                    self.resolve_function(method);
+                }
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl) {
+        // Parameters share a scope with the body, just like at runtime.
+        self.in_scope(|resolver| {
+            for param in &declaration.params {
+                resolver.declare(param);
+                resolver.define(param);
+            }
+            resolver.resolve_stmts(&declaration.body);
+        })
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign(name, value, depth) => {
+                self.resolve_expr(value);
+                self.resolve_local(&name.lexeme, depth);
+            }
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call(callee, _, arguments) => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get(object, _) => self.resolve_expr(object),
+            Expr::Interpolation(parts, _) => {
+                for part in parts {
+                    self.resolve_expr(part);
+                }
+            }
+            Expr::Set(object, _, value) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::Grouping(expr, _) | Expr::Unary(_, expr) => self.resolve_expr(expr),
+            Expr::Literal(..) => {}
+            Expr::Super(keyword, _, depth) | Expr::This(keyword, depth) => {
+                self.resolve_local(&keyword.lexeme, depth);
+            }
+            Expr::Variable(name, depth) => {
+                if let Some(false) = self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) {
+                    self.errors.push(
+                        Error::ResolverError(
+                            format!(
+                                "Can't read local variable '{}' in its own initializer.",
+                                name.lexeme
+                            ),
+                            name.span,
+                            None,
+                        )
+                        .with_help("a variable's initializer can't refer to the variable itself"),
+                    );
+                }
+                self.resolve_local(&name.lexeme, depth);
+            }
+        }
+    }
+
+    fn resolve_local(&self, name: &str, depth: &Depth) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name));
+        depth.set(found);
+    }
+
+    fn in_scope(&mut self, f: impl FnOnce(&mut Self)) {
+        self.begin_scope();
+        f(self);
+        self.end_scope();
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            self.errors.push(
+                Error::ResolverError(
+                    format!("Already a variable named '{}' in this scope.", name.lexeme),
+                    name.span,
+                    None,
+                )
+                .with_help("use a different name, or assign to the existing variable instead"),
+            );
+            return;
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve(source: &str) -> Result<Vec<Stmt>, Error> {
+        let mut scanner = Scanner::new(source);
+        scanner
+            .scan_tokens()
+            .map_err(|mut errors| errors.remove(0))?;
+        let statements = Parser::new(&scanner.tokens)
+            .parse()
+            .map_err(|mut errors| errors.remove(0))?;
+        Resolver::new()
+            .resolve(&statements)
+            .map_err(|mut errors| errors.remove(0))?;
+        Ok(statements)
+    }
+
+    #[test]
+    fn test_resolves_depths() {
+        let statements = resolve("var g; { var a; { var b; a; b; g; } }").unwrap();
+        let Stmt::Block(outer, _) = &statements[1] else {
+            panic!("expected block");
+        };
+        let Stmt::Block(inner, _) = &outer[1] else {
+            panic!("expected block");
+        };
+        let depths: Vec<_> = inner[1..]
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::ExprStmt(expr, _) => match expr.as_ref() {
+                    Expr::Variable(_, depth) => depth.get(),
+                    _ => panic!("expected variable"),
+                },
+                _ => panic!("expected expression statement"),
+            })
+            .collect();
+        assert_eq!(depths, vec![Some(1), Some(0), None]);
+    }
+
+    #[test]
+    fn test_own_initializer_is_an_error() {
+        assert!(matches!(
+            resolve("{ var a = a; }"),
+            Err(error) if error.message() == "Can't read local variable 'a' in its own initializer."
+        ));
+        // Globals may still refer to an earlier global of the same name.
+        assert!(resolve("var a = 1; var a = a;").is_ok());
+    }
+
+    #[test]
+    fn test_redeclaration_in_same_scope() {
+        assert!(matches!(
+            resolve("fun f(a) { var a; }"),
+            Err(error) if error.message() == "Already a variable named 'a' in this scope."
+        ));
+        assert!(resolve("{ var a; { var a; } }").is_ok());
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let mut scanner =
+            Scanner::new("fun f(a) { var a; }\n{ var b = b; }\nfun g() { var c; var c; }");
+        scanner.scan_tokens().unwrap();
+        let statements = Parser::new(&scanner.tokens).parse().unwrap();
+        let errors = Resolver::new().resolve(&statements).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|error| error.message()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Already a variable named 'a' in this scope.",
+                "Can't read local variable 'b' in its own initializer.",
+                "Already a variable named 'c' in this scope.",
+            ]
+        );
+    }
+}
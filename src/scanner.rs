@@ -1,51 +1,126 @@
+use crate::number::parse_decimal;
+use crate::parser_error::Error;
+use crate::span::Span;
 use crate::token::Token;
 use crate::token_type::{match_keyword, TokenType};
+use num_bigint::BigInt;
+
+const UNTERMINATED_STRING_HELP: &str = "add a closing '\"' to end the string";
 
 #[derive(Debug)]
 pub struct Scanner<'a> {
     source: &'a str,
     pub tokens: Vec<Token>,
 
+    // Byte offsets into `source`, always on a char boundary.
     start: usize,
     current: usize,
     line: i32,
+    // Column of `current`, counted in chars rather than bytes.
+    column: usize,
+    // Line and column of `start`, since scanning a token may cross lines.
+    start_line: i32,
+    start_column: usize,
+
+    // Strings whose `${...}` expressions are being scanned, innermost last.
+    interpolations: Vec<Interpolation>,
 
-    has_error: bool,
-    error_lines: Vec<i32>,
+    errors: Vec<Error>,
+}
+
+/// A string literal split around the `${...}` expressions inside it.
+#[derive(Debug)]
+struct Interpolation {
+    triple: bool,
+    // Braces opened inside the current expression and not yet closed.
+    braces: usize,
+    // Indices in `tokens` of the literal pieces scanned so far.
+    pieces: Vec<usize>,
 }
 
 impl Scanner<'_> {
-    pub fn new(source: &str) -> Scanner {
+    pub fn new(source: &str) -> Scanner<'_> {
         Scanner {
             source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
-            has_error: false,
-            error_lines: Vec::new(),
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            interpolations: Vec::new(),
+            errors: Vec::new(),
         }
     }
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    pub fn scan_tokens(&mut self) {
+    /// Scans the whole source into `tokens`, carrying on past bad input so
+    /// that every lexical error is reported at once.
+    pub fn scan_tokens(&mut self) -> Result<(), Vec<Error>> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
         }
+        if let Some(interpolation) = self.interpolations.pop() {
+            let open = &self.tokens[*interpolation.pieces.last().unwrap()];
+            let (span, text) = (open.span, open.lexeme.clone());
+            self.errors.push(
+                Error::ScannerError(
+                    "Unterminated string interpolation.".to_string(),
+                    span,
+                    text,
+                    None,
+                )
+                .with_help(UNTERMINATED_STRING_HELP),
+            );
+        }
         self.tokens.push(Token {
             token_type: TokenType::Eof,
             lexeme: "".to_string(),
             literal: "".to_string(),
-            line: self.line,
+            span: Span {
+                offset: self.current,
+                len: 0,
+                line: self.line,
+                column: self.column,
+            },
         });
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            offset: self.start,
+            len: self.current - self.start,
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    /// Records an error covering the text scanned since `start`.
+    fn error(&mut self, message: String) {
+        let error = self.lexeme_error(message);
+        self.errors.push(error);
     }
 
-    fn error(&mut self, _message: &str, line: i32) {
-        self.has_error = true;
-        self.error_lines.push(line);
+    /// An error pointing at the lexeme scanned so far.
+    fn lexeme_error(&self, message: String) -> Error {
+        let text = self.source[self.start..self.current].to_string();
+        Error::ScannerError(message, self.span(), text, None)
     }
 
     fn scan_token(&mut self) {
@@ -53,24 +128,54 @@ impl Scanner<'_> {
         match c {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.braces += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // This closes a `${`, so carry on with the rest of the string.
+                Some(interpolation) if interpolation.braces == 0 => {
+                    let triple = interpolation.triple;
+                    self.string_piece(false, triple)
+                }
+                Some(interpolation) => {
+                    interpolation.braces -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::SemiColon),
             '/' => {
-                // Special handling for slash as comments start with slash.
-                if !self.match_next('/') {
+                if self.match_next('/') {
+                    self.add_token(TokenType::SlashSlash)
+                } else {
                     self.add_token(TokenType::Slash)
+                }
+            }
+            // `//` is integer division, so line comments start with `#`.
+            '#' => {
+                while self.peek() != '\n' && !self.is_at_end() {
+                    self.advance();
+                }
+            }
+            '*' => {
+                if self.match_next('*') {
+                    self.add_token(TokenType::StarStar)
                 } else {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
+                    self.add_token(TokenType::Star)
                 }
             }
-            '*' => self.add_token(TokenType::Star),
+            '%' => self.add_token(TokenType::Percent),
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => self.add_token(TokenType::Pipe),
+            '^' => self.add_token(TokenType::Caret),
+            '~' => self.add_token(TokenType::Tilde),
             '!' => {
                 if self.match_next('=') {
                     self.add_token(TokenType::BangEqual);
@@ -88,6 +193,8 @@ impl Scanner<'_> {
             '>' => {
                 if self.match_next('=') {
                     self.add_token(TokenType::GreaterEqual)
+                } else if self.match_next('>') {
+                    self.add_token(TokenType::GreaterGreater)
                 } else {
                     self.add_token(TokenType::Greater)
                 }
@@ -95,31 +202,35 @@ impl Scanner<'_> {
             '<' => {
                 if self.match_next('=') {
                     self.add_token(TokenType::LessEqual)
+                } else if self.match_next('<') {
+                    self.add_token(TokenType::LessLess)
                 } else {
                     self.add_token(TokenType::Less)
                 }
             }
-            '"' => self.string(),
+            '"' => self.string(false),
+            'r' if self.peek() == '"' => {
+                self.advance();
+                self.string(true)
+            }
             ' ' => {}
             '\r' => {}
             '\t' => {}
-            '\n' => {
-                self.line += 1;
-            }
+            '\n' => self.new_line(),
             _ => {
                 if c.is_ascii_digit() {
                     self.number();
-                } else if c.is_alphabetic() {
+                } else if c.is_alphabetic() || c == '_' {
                     self.identifier();
                 } else {
-                    self.error("Unexpected character", self.line);
+                    self.error(format!("Unexpected character '{}'.", c));
                 }
             }
         }
     }
 
     fn identifier(&mut self) {
-        while self.peek().is_alphabetic() {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
         let identifier = &self.source[self.start..self.current];
@@ -130,27 +241,23 @@ impl Scanner<'_> {
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn match_next(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
+        if self.peek() != expected || self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
-            return false;
-        }
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
         // it is okay to unwrap here because we this should not be called when we are at the end
-        self.source.chars().nth(self.current - 1).unwrap()
+        let c = self.source[self.current..].chars().next().unwrap();
+        self.current += c.len_utf8();
+        self.column += 1;
+        c
     }
 
     fn add_token(&mut self, token_type: TokenType) {
@@ -159,7 +266,7 @@ impl Scanner<'_> {
             token_type,
             lexeme: text.to_string(),
             literal: "".to_string(),
-            line: self.line,
+            span: self.span(),
         });
     }
 
@@ -169,51 +276,618 @@ impl Scanner<'_> {
             token_type,
             lexeme: text.to_string(),
             literal,
-            line: self.line,
+            span: self.span(),
         });
     }
 
     fn peek_next(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+        let first = self.source.as_bytes()[self.start];
+        if first == b'0' && matches!(self.peek(), 'x' | 'X' | 'b' | 'B' | 'o' | 'O') {
             self.advance();
+        } else {
+            self.digits();
+            // A letter straight after the `.` is a malformed fraction, as in
+            // `1.e5`, rather than a property access on a number.
+            let next = self.peek_next();
+            if self.peek() == '.' && (next.is_alphanumeric() || next == '_') {
+                self.advance();
+                self.digits();
+            }
+            if matches!(self.peek(), 'e' | 'E') {
+                self.advance();
+                if matches!(self.peek(), '+' | '-') {
+                    self.advance();
+                }
+            }
         }
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+        // Take any trailing letters too, so `12abc` is one bad literal rather
+        // than a number followed by an identifier.
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
-        self.add_token_and_literal(
-            TokenType::Number,
-            self.source[self.start..self.current].to_string(),
-        );
+        let text = &self.source[self.start..self.current];
+        match number_literal(text) {
+            Ok((token_type, literal)) => self.add_token_and_literal(token_type, literal),
+            Err(message) => self.error(message),
+        }
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
+    fn digits(&mut self) {
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
             self.advance();
         }
+    }
+
+    /// Scans a string whose opening quote has been consumed. Raw strings
+    /// (`r"..."`) are taken verbatim; `"""` strings may span lines and have
+    /// their common indentation removed.
+    fn string(&mut self, raw: bool) {
+        let triple = self.source[self.current..].starts_with("\"\"");
+        if triple {
+            self.advance();
+            self.advance();
+        }
+        if !raw {
+            self.interpolations.push(Interpolation {
+                triple,
+                braces: 0,
+                pieces: Vec::new(),
+            });
+        }
+        self.string_piece(raw, triple);
+    }
+
+    /// Scans string text up to the closing quotes or, unless the string is
+    /// raw, up to the next `${`. Each piece becomes its own token, and the
+    /// literal values are only finished once the closing quotes are reached.
+    fn string_piece(&mut self, raw: bool, triple: bool) {
+        let body_start = self.current;
+        let closing = if triple { "\"\"\"" } else { "\"" };
+        loop {
+            let rest = &self.source[self.current..];
+            if rest.is_empty() {
+                if !raw {
+                    self.interpolations.pop();
+                }
+                let error = self
+                    .lexeme_error("Unterminated string.".to_string())
+                    .with_help(UNTERMINATED_STRING_HELP);
+                self.errors.push(error);
+                return;
+            }
+            if rest.starts_with(closing) || (!raw && rest.starts_with("${")) {
+                break;
+            }
+            let c = self.advance();
+            // Skip the escaped character so an escaped quote doesn't end the string.
+            let c = if c == '\\' && !raw && !self.is_at_end() {
+                self.advance()
+            } else {
+                c
+            };
+            if c == '\n' {
+                self.new_line();
+            }
+        }
 
-        if self.is_at_end() {
-            self.error("Unterminated string", self.line);
+        let body = self.source[body_start..self.current].to_string();
+        if !self.source[self.current..].starts_with(closing) {
+            self.advance();
+            self.advance();
+            self.add_token_and_literal(TokenType::Interpolation, body);
+            let interpolation = self.interpolations.last_mut().unwrap();
+            interpolation.pieces.push(self.tokens.len() - 1);
             return;
         }
+        for _ in 0..closing.len() {
+            self.advance();
+        }
+        self.add_token_and_literal(TokenType::String, body);
+        if raw {
+            if triple {
+                let literal = &mut self.tokens.last_mut().unwrap().literal;
+                *literal = strip_indent(vec![std::mem::take(literal)]).remove(0);
+            }
+            return;
+        }
+        let mut interpolation = self.interpolations.pop().unwrap();
+        interpolation.pieces.push(self.tokens.len() - 1);
+        self.finish_string(interpolation);
+    }
 
-        // Consume the closing quote
-        self.advance();
+    /// Turns the raw text of each piece of a string into its value.
+    fn finish_string(&mut self, interpolation: Interpolation) {
+        let mut pieces: Vec<String> = interpolation
+            .pieces
+            .iter()
+            .map(|&index| std::mem::take(&mut self.tokens[index].literal))
+            .collect();
+        if interpolation.triple {
+            pieces = strip_indent(pieces);
+        }
+        for (&index, piece) in interpolation.pieces.iter().zip(pieces) {
+            match unescape(&piece) {
+                Ok(value) => self.tokens[index].literal = value,
+                Err(message) => {
+                    let token = &self.tokens[index];
+                    let (span, text) = (token.span, token.lexeme.clone());
+                    self.errors
+                        .push(Error::ScannerError(message, span, text, None));
+                }
+            }
+        }
+    }
+}
+
+/// Removes the line break after an opening `"""`, the line holding the
+/// closing `"""` if it is blank, and the indentation shared by every
+/// non-blank line. The string comes in pieces split around its `${...}`
+/// expressions, which count as text when looking for blank lines.
+fn strip_indent(pieces: Vec<String>) -> Vec<String> {
+    let mut pieces: Vec<String> = pieces
+        .into_iter()
+        .map(|piece| piece.replace("\r\n", "\n"))
+        .collect();
+    let last = pieces.len() - 1;
+    if pieces[0].starts_with('\n') {
+        pieces[0].remove(0);
+    }
+    if let Some(newline) = pieces[last].rfind('\n') {
+        if pieces[last][newline + 1..].trim().is_empty() {
+            pieces[last].truncate(newline);
+        }
+    }
+
+    // Every line starts either at the very beginning or just after a newline.
+    let mut line_starts = vec![(0, 0)];
+    for (index, piece) in pieces.iter().enumerate() {
+        line_starts.extend(
+            piece
+                .match_indices('\n')
+                .map(|(offset, _)| (index, offset + 1)),
+        );
+    }
+    let whitespace = |text: &str| text.len() - text.trim_start_matches([' ', '\t']).len();
+    let indent = line_starts
+        .iter()
+        .filter(|&&(index, offset)| {
+            let rest = &pieces[index][offset..];
+            let line = rest.split('\n').next().unwrap_or("");
+            let ends_in_text = rest.contains('\n') || index == last;
+            !(ends_in_text && line.trim().is_empty())
+        })
+        .map(|&(index, offset)| whitespace(&pieces[index][offset..]))
+        .min()
+        .unwrap_or(0);
+    // Strip from the back so the earlier offsets stay valid.
+    for &(index, offset) in line_starts.iter().rev() {
+        let strip = whitespace(&pieces[index][offset..]).min(indent);
+        pieces[index].replace_range(offset..offset + strip, "");
+    }
+    pieces
+}
+
+/// Replaces the escape sequences in a string literal's body.
+fn unescape(body: &str) -> Result<String, String> {
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some('\\') => value.push('\\'),
+            Some('"') => value.push('"'),
+            Some('$') => value.push('$'),
+            Some('u') => {
+                let rest = chars.as_str();
+                let escape = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(digits, _)| digits);
+                let c = escape
+                    .filter(|digits| (1..=6).contains(&digits.len()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+                match (escape, c) {
+                    (Some(digits), Some(c)) => {
+                        value.push(c);
+                        // Skip past the braces and digits.
+                        chars = rest[digits.len() + 2..].chars();
+                    }
+                    (Some(digits), None) => {
+                        return Err(format!("Invalid unicode escape '\\u{{{}}}'.", digits))
+                    }
+                    (None, _) => {
+                        return Err("Unicode escape must look like '\\u{XXXX}'.".to_string())
+                    }
+                }
+            }
+            Some(other) => return Err(format!("Invalid escape sequence '\\{}'.", other)),
+            None => return Err("Unfinished escape sequence.".to_string()),
+        }
+    }
+    Ok(value)
+}
+
+/// Checks a scanned number literal, returning it in the plain decimal form
+/// the parser reads. Literals without a fraction or exponent are integers,
+/// and a `d` suffix makes an exact decimal.
+fn number_literal(text: &str) -> Result<(TokenType, String), String> {
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => Some((16, "hexadecimal")),
+        Some("0b" | "0B") => Some((2, "binary")),
+        Some("0o" | "0O") => Some((8, "octal")),
+        _ => None,
+    };
+    // A `_` separator is only allowed between two digits.
+    let is_digit = |byte: &u8| (*byte as char).is_digit(radix.map_or(10, |(radix, _)| radix));
+    let bytes = text.as_bytes();
+    let separators_ok = bytes.iter().enumerate().all(|(i, &byte)| {
+        byte != b'_' || (i > 0 && is_digit(&bytes[i - 1]) && bytes.get(i + 1).is_some_and(is_digit))
+    });
+    if !separators_ok {
+        return Err(format!("Misplaced digit separator in '{}'.", text));
+    }
+    let digits = text.replace('_', "");
+
+    if let Some((radix, name)) = radix {
+        let digits = &digits[2..];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return Err(format!("Invalid {} literal '{}'.", name, text));
+        }
+        let value = BigInt::parse_bytes(digits.as_bytes(), radix).unwrap();
+        return Ok((TokenType::Integer, value.to_string()));
+    }
+
+    let (digits, token_type) = match digits.strip_suffix('d') {
+        Some(digits) => (digits.to_string(), TokenType::Decimal),
+        None => (digits, TokenType::Number),
+    };
+
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(e) => (&digits[..e], Some(&digits[e + 1..])),
+        None => (&digits[..], None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let exponent = exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent));
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(integer) || !fraction.is_none_or(is_digits) || !exponent.is_none_or(is_digits) {
+        return Err(format!("Invalid number literal '{}'.", text));
+    }
+    if token_type == TokenType::Decimal && parse_decimal(&digits).is_none() {
+        return Err(format!("Decimal literal '{}' is too large.", text));
+    }
+    if token_type == TokenType::Number && fraction.is_none() && exponent.is_none() {
+        return Ok((TokenType::Integer, digits));
+    }
+    Ok((token_type, digits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_spans() {
+        let mut scanner = Scanner::new("var a = 1;\n  print \"two\nlines\";");
+        scanner.scan_tokens().unwrap();
+        let spans: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.span))
+            .collect();
+        let span = |offset, len, line, column| Span {
+            offset,
+            len,
+            line,
+            column,
+        };
+        assert_eq!(
+            spans,
+            vec![
+                ("var", span(0, 3, 1, 1)),
+                ("a", span(4, 1, 1, 5)),
+                ("=", span(6, 1, 1, 7)),
+                ("1", span(8, 1, 1, 9)),
+                (";", span(9, 1, 1, 10)),
+                ("print", span(13, 5, 2, 3)),
+                ("\"two\nlines\"", span(19, 11, 2, 9)),
+                (";", span(30, 1, 3, 7)),
+                ("", span(31, 0, 3, 8)),
+            ]
+        );
+    }
 
-        // Extract the string value (without the quotes)
-        let value = self.source[(self.start + 1)..(self.current - 1)].to_string();
+    #[test]
+    fn test_reports_every_lexical_error() {
+        let mut scanner = Scanner::new("var a = 1 @ 2;\nprint \"open;");
+        let errors = scanner.scan_tokens().unwrap_err();
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::ScannerError(message, span, text, _) => {
+                    (message.as_str(), span.line, span.column, text.as_str())
+                }
+                _ => panic!("expected a scanner error"),
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("Unexpected character '@'.", 1, 11, "@"),
+                ("Unterminated string.", 2, 7, "\"open;"),
+            ]
+        );
+    }
 
-        // Add the string token
-        self.add_token_and_literal(TokenType::String, value);
+    #[test]
+    fn test_unicode_source() {
+        let mut scanner = Scanner::new("var née = \"héllo 🌍\"; print née;");
+        scanner.scan_tokens().unwrap();
+        let tokens: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.span.column))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                ("var", 1),
+                ("née", 5),
+                ("=", 9),
+                ("\"héllo 🌍\"", 11),
+                (";", 20),
+                ("print", 22),
+                ("née", 28),
+                (";", 31),
+                ("", 32),
+            ]
+        );
+        assert_eq!(scanner.tokens[3].literal, "héllo 🌍");
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let mut scanner = Scanner::new("x1 my_var _private");
+        scanner.scan_tokens().unwrap();
+        let lexemes: Vec<_> = scanner.tokens[..3]
+            .iter()
+            .map(|token| (token.token_type.clone(), token.lexeme.as_str()))
+            .collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                (TokenType::Identifier, "x1"),
+                (TokenType::Identifier, "my_var"),
+                (TokenType::Identifier, "_private"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let mut scanner = Scanner::new("3.14 1e9 2.5E-3 1_000_000 0xFF 0b1010 0o17 0xf_f 0.1d 2d 1_000.5e-2d 0x1d 99999999999999999999");
+        scanner.scan_tokens().unwrap();
+        let literals: Vec<_> = scanner
+            .tokens
+            .iter()
+            .filter(|token| token.token_type != TokenType::Eof)
+            .map(|token| (token.token_type.clone(), token.literal.as_str()))
+            .collect();
+        let float = |literal| (TokenType::Number, literal);
+        let int = |literal| (TokenType::Integer, literal);
+        assert_eq!(
+            literals,
+            vec![
+                float("3.14"),
+                float("1e9"),
+                float("2.5E-3"),
+                int("1000000"),
+                int("255"),
+                int("10"),
+                int("15"),
+                int("255"),
+                (TokenType::Decimal, "0.1"),
+                (TokenType::Decimal, "2"),
+                (TokenType::Decimal, "1000.5e-2"),
+                int("29"),
+                int("99999999999999999999"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_number_literals() {
+        for (source, message) in [
+            ("12abc", "Invalid number literal '12abc'."),
+            ("1e", "Invalid number literal '1e'."),
+            ("1e+", "Invalid number literal '1e+'."),
+            ("1__0", "Misplaced digit separator in '1__0'."),
+            ("1_", "Misplaced digit separator in '1_'."),
+            ("1_.5", "Misplaced digit separator in '1_.5'."),
+            ("1e_5", "Misplaced digit separator in '1e_5'."),
+            ("0x_ff", "Misplaced digit separator in '0x_ff'."),
+            ("0x", "Invalid hexadecimal literal '0x'."),
+            ("0b102", "Invalid binary literal '0b102'."),
+            ("0o8", "Invalid octal literal '0o8'."),
+            ("1.5x", "Invalid number literal '1.5x'."),
+            ("1.e5", "Invalid number literal '1.e5'."),
+            ("7.foo", "Invalid number literal '7.foo'."),
+            ("1._5", "Misplaced digit separator in '1._5'."),
+            ("1e9999d", "Decimal literal '1e9999d' is too large."),
+        ] {
+            let errors = Scanner::new(source).scan_tokens().unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert_eq!(errors[0].message(), message);
+        }
+    }
+
+    fn string_literal(source: &str) -> Result<String, String> {
+        let mut scanner = Scanner::new(source);
+        match scanner.scan_tokens() {
+            Ok(()) => Ok(scanner.tokens[0].literal.clone()),
+            Err(errors) => Err(errors[0].message().to_string()),
+        }
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            string_literal(r#""a\tb\n\"q\" \\ \u{e9}\u{1F600}""#),
+            Ok("a\tb\n\"q\" \\ é😀".to_string())
+        );
+        assert_eq!(
+            string_literal(r#""\q""#),
+            Err("Invalid escape sequence '\\q'.".to_string())
+        );
+        assert_eq!(
+            string_literal(r#""\u{D800}""#),
+            Err("Invalid unicode escape '\\u{D800}'.".to_string())
+        );
+        assert_eq!(
+            string_literal(r#""\u41""#),
+            Err("Unicode escape must look like '\\u{XXXX}'.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        assert_eq!(
+            string_literal(r#"r"C:\new\table""#),
+            Ok(r"C:\new\table".to_string())
+        );
+        let mut scanner = Scanner::new("r + r\"\"");
+        scanner.scan_tokens().unwrap();
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(scanner.tokens[2].token_type, TokenType::String);
+    }
+
+    #[test]
+    fn test_triple_quoted_strings() {
+        let source = "\"\"\"\n    Dear reader,\n\n      \\\"Indented\\\"\n    Bye\n    \"\"\"";
+        assert_eq!(
+            string_literal(source),
+            Ok("Dear reader,\n\n  \"Indented\"\nBye".to_string())
+        );
+        assert_eq!(
+            string_literal("\"\"\"one line\"\"\""),
+            Ok("one line".to_string())
+        );
+        assert_eq!(string_literal("\"\"\"\"\"\""), Ok("".to_string()));
+        assert_eq!(
+            string_literal("\"\"\"open"),
+            Err("Unterminated string.".to_string())
+        );
+
+        let mut scanner = Scanner::new("\"\"\"\na\nb\"\"\";");
+        scanner.scan_tokens().unwrap();
+        assert_eq!(scanner.tokens[1].span.line, 3);
+    }
+
+    #[test]
+    fn test_interpolation_tokens() {
+        let mut scanner = Scanner::new(r#""Hi ${name}, ${ "<${x}>" } \${no}""#);
+        scanner.scan_tokens().unwrap();
+        let tokens: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| (token.token_type.clone(), token.literal.as_str()))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::Interpolation, "Hi "),
+                (TokenType::Identifier, ""),
+                (TokenType::Interpolation, ", "),
+                (TokenType::Interpolation, "<"),
+                (TokenType::Identifier, ""),
+                (TokenType::String, ">"),
+                (TokenType::String, " ${no}"),
+                (TokenType::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolated_triple_quoted_string() {
+        let source = "\"\"\"\n    Dear ${name},\n      ${body}\n    \"\"\"";
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens().unwrap();
+        let pieces: Vec<_> = scanner
+            .tokens
+            .iter()
+            .filter(|token| token.token_type != TokenType::Identifier)
+            .map(|token| token.literal.as_str())
+            .collect();
+        assert_eq!(pieces, vec!["Dear ", ",\n  ", "", ""]);
+    }
+
+    #[test]
+    fn test_unterminated_interpolation() {
+        let errors = Scanner::new("\"a ${b").scan_tokens().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message(), "Unterminated string interpolation.");
+    }
+
+    #[test]
+    fn test_operators_and_comments() {
+        let mut scanner = Scanner::new(
+            "a // b ** c << 1 >> ~d & e | f ^ g;\n# note\nh; # note\n(i) # j\nf() # k\n7 // 2",
+        );
+        scanner.scan_tokens().unwrap();
+        let types: Vec<_> = scanner
+            .tokens
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        use TokenType::*;
+        assert_eq!(
+            types,
+            vec![
+                Identifier,
+                SlashSlash,
+                Identifier,
+                StarStar,
+                Identifier,
+                LessLess,
+                Integer,
+                GreaterGreater,
+                Tilde,
+                Identifier,
+                Ampersand,
+                Identifier,
+                Pipe,
+                Identifier,
+                Caret,
+                Identifier,
+                SemiColon,
+                Identifier,
+                SemiColon,
+                LeftParen,
+                Identifier,
+                RightParen,
+                Identifier,
+                LeftParen,
+                RightParen,
+                Integer,
+                SlashSlash,
+                Integer,
+                Eof,
+            ]
+        );
     }
 }
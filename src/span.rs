@@ -0,0 +1,64 @@
+/// A region of the source text, used to point errors at the code that
+/// caused them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub offset: usize,
+    /// Length in bytes.
+    pub len: usize,
+    /// 1-based line of the first character.
+    pub line: i32,
+    /// 1-based column of the first character.
+    pub column: usize,
+}
+
+impl Span {
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.offset <= other.offset {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let end = (first.offset + first.len).max(last.offset + last.len);
+        Span {
+            len: end - first.offset,
+            ..first
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_to() {
+        let left = Span {
+            offset: 4,
+            len: 1,
+            line: 1,
+            column: 5,
+        };
+        let right = Span {
+            offset: 8,
+            len: 3,
+            line: 1,
+            column: 9,
+        };
+        let expected = Span {
+            offset: 4,
+            len: 7,
+            line: 1,
+            column: 5,
+        };
+        assert_eq!(left.to(right), expected);
+        assert_eq!(right.to(left), expected);
+    }
+}
@@ -1,10 +1,60 @@
 use crate::expr::Expr;
+use crate::span::Span;
 use crate::token::Token;
+use std::rc::Rc;
 
 #[derive(Debug)]
-pub enum Stmt<'a> {
-    //
-    ExprStmt(Box<Expr<'a>>),
-    PrintStmt(Box<Expr<'a>>),
-    VarStmt(&'a Token, Option<Box<Expr<'a>>>),
+pub enum Stmt {
+    // Statements without a token to point at carry the span they were
+    // parsed from.
+    ExprStmt(Box<Expr>, Span),
+    PrintStmt(Box<Expr>, Span),
+    VarStmt(Token, Option<Box<Expr>>, Span),
+    Block(Vec<Stmt>, Span),
+    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>, Span),
+    While(Box<Expr>, Box<Stmt>, Span),
+    // initializer, condition, increment, body
+    For(
+        Option<Box<Stmt>>,
+        Option<Box<Expr>>,
+        Option<Box<Expr>>,
+        Box<Stmt>,
+        Span,
+    ),
+    Break(Token),
+    Continue(Token),
+    Function(Rc<FunctionDecl>),
+    Return(Token, Option<Box<Expr>>),
+    // name, superclass, methods
+    Class(Token, Option<Box<Expr>>, Vec<Rc<FunctionDecl>>),
+}
+
+impl Stmt {
+    /// The region of source this statement was parsed from. Function and
+    /// class declarations point at their name.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::ExprStmt(_, span)
+            | Stmt::PrintStmt(_, span)
+            | Stmt::VarStmt(_, _, span)
+            | Stmt::Block(_, span)
+            | Stmt::If(_, _, _, span)
+            | Stmt::While(_, _, span)
+            | Stmt::For(_, _, _, _, span) => *span,
+            Stmt::Break(keyword) | Stmt::Continue(keyword) => keyword.span,
+            Stmt::Return(keyword, value) => match value {
+                Some(value) => keyword.span.to(value.span()),
+                None => keyword.span,
+            },
+            Stmt::Function(declaration) => declaration.name.span,
+            Stmt::Class(name, _, _) => name.span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
 }
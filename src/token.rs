@@ -1,11 +1,12 @@
+use crate::span::Span;
 use crate::token_type::TokenType;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: String,
-    pub line: i32,
+    pub span: Span,
 }
 
 impl std::fmt::Display for Token {
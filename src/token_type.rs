@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
@@ -13,7 +13,11 @@ pub enum TokenType {
     SemiColon,
     Slash,
     Star,
-
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
     // One or two character tokens.
     Bang,
     BangEqual,
@@ -23,15 +27,25 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    SlashSlash,
+    StarStar,
 
     // Literals.
     Identifier,
     String,
+    // The text of a string literal before one of its `${...}` expressions.
+    Interpolation,
+    Integer,
+    Decimal,
     Number,
 
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
@@ -53,7 +67,9 @@ pub enum TokenType {
 pub fn match_keyword(keyword: &str) -> Option<TokenType> {
     match keyword {
         "and" => Some(TokenType::And),
+        "break" => Some(TokenType::Break),
         "class" => Some(TokenType::Class),
+        "continue" => Some(TokenType::Continue),
         "else" => Some(TokenType::Else),
         "false" => Some(TokenType::False),
         "for" => Some(TokenType::For),